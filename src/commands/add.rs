@@ -1,24 +1,34 @@
 use std::path::Path;
 use anyhow::Result;
 use crate::core::Repository;
-use crate::utils::files::{find_files_in_directory, is_ignored};
+use crate::core::worktree::head_files;
+use crate::core::attributes::Attributes;
+use crate::utils::files::IgnoreRules;
 
 pub fn execute(files: Vec<String>, all: bool) -> Result<()> {
     let repo = Repository::find_repository()?;
     let mut index = repo.load_index()?;
+    let ignore_rules = IgnoreRules::load(&repo)?;
+    let attributes = Attributes::load(&repo)?;
 
     if all {
         // Add all files in the repository
-        let all_files = find_files_in_directory(&repo.root)?;
+        let all_files = ignore_rules.files()?;
         for file_path in all_files {
-            if !is_ignored(&file_path) {
-                match index.add_file(&file_path, &repo.root) {
-                    Ok(_) => {
-                        let relative_path = file_path.strip_prefix(&repo.root)?;
-                        println!("Added: {}", relative_path.display());
-                    }
-                    Err(e) => eprintln!("Warning: Could not add {}: {}", file_path.display(), e),
+            match index.add_file(&repo, &file_path, &attributes) {
+                Ok(_) => {
+                    let relative_path = file_path.strip_prefix(&repo.root)?;
+                    println!("Added: {}", relative_path.display());
                 }
+                Err(e) => eprintln!("Warning: Could not add {}: {}", file_path.display(), e),
+            }
+        }
+
+        // Stage the deletion of committed files that are gone
+        for path in head_files(&repo)?.into_keys() {
+            if repo.root.join(&path).symlink_metadata().is_err() {
+                index.stage_removal(&path);
+                println!("Removed: {}", path);
             }
         }
     } else if files.is_empty() {
@@ -33,17 +43,17 @@ pub fn execute(files: Vec<String>, all: bool) -> Result<()> {
                 repo.root.join(file_path)
             };
 
-            if !full_path.exists() {
+            if full_path.symlink_metadata().is_err() {
                 eprintln!("Warning: File does not exist: {}", file_str);
                 continue;
             }
 
-            if is_ignored(&full_path) {
+            if ignore_rules.is_ignored(&full_path)? {
                 eprintln!("Warning: File is ignored: {}", file_str);
                 continue;
             }
 
-            match index.add_file(&full_path, &repo.root) {
+            match index.add_file(&repo, &full_path, &attributes) {
                 Ok(_) => println!("Added: {}", file_str),
                 Err(e) => eprintln!("Error adding {}: {}", file_str, e),
             }
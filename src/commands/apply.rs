@@ -0,0 +1,194 @@
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use anyhow::Result;
+use colored::*;
+use crate::core::{Repository, Index, IndexEntry, StatData, Object, Blob, FileMode};
+use crate::utils::patch::{parse_patch, apply_patch, format_rejects, FilePatch};
+
+pub fn execute(
+    patch_file: Option<String>,
+    cached: bool,
+    index_too: bool,
+    check: bool,
+    strip: usize,
+    fuzz: usize,
+) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let mut index = repo.load_index()?;
+
+    let text = match patch_file.as_deref() {
+        None | Some("-") => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+        Some(path) => fs::read_to_string(path)?,
+    };
+
+    let patches = parse_patch(&text, strip)?;
+    if patches.is_empty() {
+        return Err(anyhow::anyhow!("No valid patches in input"));
+    }
+
+    let update_worktree = !cached;
+    let update_index = cached || index_too;
+    let mut failed_hunks = 0;
+
+    // Apply everything in memory first so a missing or conflicting file
+    // aborts before anything is written
+    let mut outcomes = Vec::new();
+    for patch in &patches {
+        let path = patch.path();
+        let worktree = if update_worktree {
+            let result = apply_patch(&worktree_content(&repo, patch)?, patch, fuzz);
+            report(path, &result.messages);
+            failed_hunks += result.rejected.len();
+            Some(result)
+        } else {
+            None
+        };
+        let staged = if update_index {
+            let result = apply_patch(&staged_content(&repo, &index, patch)?, patch, fuzz);
+            if !update_worktree {
+                report(path, &result.messages);
+                failed_hunks += result.rejected.len();
+            }
+            Some(result)
+        } else {
+            None
+        };
+        outcomes.push((patch, worktree, staged));
+    }
+
+    if !check {
+        for (patch, worktree, staged) in outcomes {
+            let rejected = match (&worktree, &staged) {
+                (Some(result), _) | (None, Some(result)) => &result.rejected,
+                (None, None) => continue,
+            };
+            if !rejected.is_empty() {
+                let reject_file = repo.root.join(format!("{}.rej", patch.path()));
+                fs::write(&reject_file, format_rejects(patch, rejected))?;
+                eprintln!("Rejected hunks written to {}", reject_file.display());
+            }
+
+            if let Some(result) = worktree {
+                write_worktree(&repo, patch, &result.content)?;
+            }
+            // The index is only updated for files whose hunks all applied
+            if let Some(result) = staged.filter(|result| result.rejected.is_empty()) {
+                write_index(&repo, &mut index, patch, result.content)?;
+            }
+        }
+        repo.save_index(&index)?;
+    }
+
+    if failed_hunks > 0 {
+        return Err(anyhow::anyhow!("Patch failed: {} hunk(s) rejected", failed_hunks));
+    }
+
+    if check {
+        println!("Patch applies cleanly");
+    } else {
+        println!("Applied patch to {} file(s)", patches.len());
+    }
+    Ok(())
+}
+
+fn report(path: &str, messages: &[String]) {
+    for message in messages {
+        if message.contains("FAILED") {
+            eprintln!("{}: {}", path, message.red());
+        } else {
+            eprintln!("{}: {}", path, message);
+        }
+    }
+}
+
+fn worktree_content(repo: &Repository, patch: &FilePatch) -> Result<String> {
+    match &patch.old_path {
+        None => {
+            let target = repo.root.join(patch.path());
+            if target.exists() {
+                return Err(anyhow::anyhow!("{}: already exists in working directory", patch.path()));
+            }
+            Ok(String::new())
+        }
+        Some(old_path) => {
+            let source = repo.root.join(old_path);
+            if !source.exists() {
+                return Err(anyhow::anyhow!("{}: does not exist in working directory", old_path));
+            }
+            Ok(fs::read_to_string(source)?)
+        }
+    }
+}
+
+fn write_worktree(repo: &Repository, patch: &FilePatch, content: &str) -> Result<()> {
+    if let Some(old_path) = &patch.old_path {
+        if patch.new_path.as_ref() != Some(old_path) {
+            fs::remove_file(repo.root.join(old_path))?;
+        }
+    }
+
+    if let Some(new_path) = &patch.new_path {
+        let target = repo.root.join(new_path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(target, content)?;
+    } else if !content.is_empty() {
+        // The deletion left lines behind; keep them rather than lose data
+        fs::write(repo.root.join(patch.path()), content)?;
+    }
+    Ok(())
+}
+
+/// Reads the staged version of a file, falling back to the HEAD commit.
+fn staged_content(repo: &Repository, index: &Index, patch: &FilePatch) -> Result<String> {
+    let Some(old_path) = &patch.old_path else {
+        if index.entries.get(&PathBuf::from(patch.path())).is_some_and(|entry| !entry.removed) {
+            return Err(anyhow::anyhow!("{}: already exists in index", patch.path()));
+        }
+        return Ok(String::new());
+    };
+
+    let hash = match index.entries.get(&PathBuf::from(old_path)) {
+        Some(entry) if entry.removed => None,
+        Some(entry) => Some(entry.hash.clone()),
+        None => match repo.get_head()? {
+            Some(head) => {
+                let commit_obj = repo.load_object(&head)?;
+                let commit = commit_obj.as_commit().unwrap();
+                let tree_obj = repo.load_object(&commit.tree)?;
+                let tree = tree_obj.as_tree().unwrap();
+                tree.entries.get(old_path).map(|entry| entry.hash.clone())
+            }
+            None => None,
+        },
+    };
+
+    let hash = hash.ok_or_else(|| anyhow::anyhow!("{}: does not exist in index", old_path))?;
+    let blob_obj = repo.load_object(&hash)?;
+    let blob = blob_obj.as_blob()
+        .ok_or_else(|| anyhow::anyhow!("{}: index entry is not a blob", old_path))?;
+    Ok(blob.to_string()?)
+}
+
+fn write_index(repo: &Repository, index: &mut Index, patch: &FilePatch, content: String) -> Result<()> {
+    if let Some(old_path) = &patch.old_path {
+        if patch.new_path.as_ref() != Some(old_path) {
+            index.stage_removal(old_path);
+        }
+    }
+
+    if let Some(new_path) = &patch.new_path {
+        let hash = repo.store_object(&Object::Blob(Blob::from_string(content)))?;
+        let path = PathBuf::from(new_path);
+        // Patches do not carry modes; keep the one the file has
+        let mode = FileMode::of(&repo.root.join(&path)).unwrap_or_default();
+        index.entries.insert(path.clone(), IndexEntry { path, hash, stat: StatData::default(), mode, removed: false });
+    }
+    Ok(())
+}
@@ -0,0 +1,279 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use anyhow::Result;
+use crate::core::{Repository, Commit};
+use crate::core::revwalk::RevWalk;
+use crate::commands::checkout::switch_worktree;
+use crate::utils::pretty::signature_lines;
+
+/// How a tested commit was judged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mark {
+    Good,
+    Bad,
+    Skip,
+}
+
+impl Mark {
+    fn name(&self) -> &'static str {
+        match self {
+            Mark::Good => "good",
+            Mark::Bad => "bad",
+            Mark::Skip => "skip",
+        }
+    }
+}
+
+/// Where the search stands after the latest mark.
+enum Step {
+    /// Still missing a good or a bad commit
+    Waiting,
+    /// A candidate has been checked out for testing
+    Testing,
+    /// The search is over
+    Done,
+}
+
+pub fn start(bad: Option<String>, good: Vec<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    if is_bisecting(&repo) {
+        return Err(anyhow::anyhow!("Already bisecting; use \"nvcs bisect reset\" to start over"));
+    }
+    if !repo.load_index()?.entries.is_empty() {
+        return Err(anyhow::anyhow!("You have staged changes; commit them before bisecting"));
+    }
+
+    let original = match repo.get_current_branch()? {
+        Some(branch) => branch,
+        None => repo.get_head()?.ok_or_else(|| anyhow::anyhow!("No commits yet"))?,
+    };
+    // Resolve everything up front so a typo leaves no half-started bisect
+    let bad = bad.map(|revision| repo.resolve_revision(&revision)).transpose()?;
+    let good: Vec<String> = good.iter().map(|revision| repo.resolve_revision(revision)).collect::<Result<_>>()?;
+
+    fs::write(state_file(&repo, "START"), format!("{}\n", original))?;
+    append_log(&repo, "nvcs bisect start")?;
+    if let Some(bad) = bad {
+        record(&repo, Mark::Bad, &bad)?;
+    }
+    for good in &good {
+        record(&repo, Mark::Good, good)?;
+    }
+
+    advance(&repo)?;
+    Ok(())
+}
+
+pub fn mark(mark: Mark, revisions: Vec<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    ensure_bisecting(&repo)?;
+
+    let revisions = if revisions.is_empty() { vec!["HEAD".to_string()] } else { revisions };
+    if mark != Mark::Skip && revisions.len() > 1 {
+        return Err(anyhow::anyhow!("'bisect {}' takes only one revision", mark.name()));
+    }
+    for revision in &revisions {
+        let hash = repo.resolve_revision(revision)?;
+        record(&repo, mark, &hash)?;
+    }
+
+    advance(&repo)?;
+    Ok(())
+}
+
+pub fn reset() -> Result<()> {
+    let repo = Repository::find_repository()?;
+    if !is_bisecting(&repo) {
+        println!("We are not bisecting.");
+        return Ok(());
+    }
+
+    let original = fs::read_to_string(state_file(&repo, "START"))?.trim().to_string();
+    if repo.list_branches()?.contains(&original) {
+        switch_worktree(&repo, &repo.resolve_revision(&original)?)?;
+        repo.checkout_branch(&original)?;
+        println!("Switched to branch '{}'", original);
+    } else {
+        switch_worktree(&repo, &original)?;
+        fs::write(&repo.head_file, format!("{}\n", original))?;
+        println!("HEAD is now at {}", &original[..8]);
+    }
+
+    for name in ["START", "BAD", "GOOD", "SKIP", "LOG"] {
+        let file = state_file(&repo, name);
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn log() -> Result<()> {
+    let repo = Repository::find_repository()?;
+    ensure_bisecting(&repo)?;
+    print!("{}", fs::read_to_string(state_file(&repo, "LOG"))?);
+    Ok(())
+}
+
+/// Marks each candidate by the exit status of `command`: 0 is good, 125
+/// skips, anything else below 128 is bad and the rest abort the run.
+pub fn run(command: Vec<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    ensure_bisecting(&repo)?;
+    let Some((program, args)) = command.split_first() else {
+        return Err(anyhow::anyhow!("bisect run needs a command"));
+    };
+    let display = command.join(" ");
+
+    if read_hashes(&repo, "BAD")?.is_empty() || read_hashes(&repo, "GOOD")?.is_empty() {
+        return Err(anyhow::anyhow!("bisect run needs both a good and a bad commit"));
+    }
+    loop {
+        println!("running '{}'", display);
+        let status = Command::new(program).args(args).current_dir(&repo.root).status()?;
+        let mark = match status.code() {
+            Some(0) => Mark::Good,
+            Some(125) => Mark::Skip,
+            Some(code) if code < 128 => Mark::Bad,
+            _ => return Err(anyhow::anyhow!("bisect run failed: '{}' exited with {}", display, status)),
+        };
+
+        let head = repo.get_head()?.ok_or_else(|| anyhow::anyhow!("HEAD is not set"))?;
+        record(&repo, mark, &head)?;
+        if let Step::Done = advance(&repo)? {
+            return Ok(());
+        }
+    }
+}
+
+fn state_file(repo: &Repository, name: &str) -> PathBuf {
+    repo.nvcs_dir.join(format!("BISECT_{}", name))
+}
+
+fn is_bisecting(repo: &Repository) -> bool {
+    state_file(repo, "START").exists()
+}
+
+fn ensure_bisecting(repo: &Repository) -> Result<()> {
+    if is_bisecting(repo) {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("You need to start by \"nvcs bisect start\""))
+    }
+}
+
+fn read_hashes(repo: &Repository, name: &str) -> Result<Vec<String>> {
+    let file = state_file(repo, name);
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(fs::read_to_string(file)?.lines().map(str::to_string).filter(|line| !line.is_empty()).collect())
+}
+
+fn append_log(repo: &Repository, line: &str) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(state_file(repo, "LOG"))?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+fn record(repo: &Repository, mark: Mark, hash: &str) -> Result<()> {
+    match mark {
+        Mark::Bad => fs::write(state_file(repo, "BAD"), format!("{}\n", hash))?,
+        Mark::Good | Mark::Skip => {
+            let name = if mark == Mark::Good { "GOOD" } else { "SKIP" };
+            let mut file = OpenOptions::new().create(true).append(true).open(state_file(repo, name))?;
+            writeln!(file, "{}", hash)?;
+        }
+    }
+
+    let commit = repo.load_commit(hash)?;
+    append_log(repo, &format!("# {}: [{}] {}", mark.name(), &hash[..8], subject(&commit)))?;
+    append_log(repo, &format!("nvcs bisect {} {}", mark.name(), hash))
+}
+
+/// Checks out the next commit to test, or reports the first bad commit.
+fn advance(repo: &Repository) -> Result<Step> {
+    let bad = read_hashes(repo, "BAD")?.pop();
+    let good = read_hashes(repo, "GOOD")?;
+    let Some(bad) = bad.filter(|_| !good.is_empty()) else {
+        println!("status: waiting for both good and bad commits");
+        return Ok(Step::Waiting);
+    };
+    let skipped: HashSet<String> = read_hashes(repo, "SKIP")?.into_iter().collect();
+
+    let mut walk = RevWalk::new(repo);
+    walk.push(bad.clone());
+    for hash in &good {
+        walk.hide(hash.clone());
+    }
+    let candidates: HashMap<String, Commit> = walk.commits()?.into_iter().collect();
+    if !candidates.contains_key(&bad) {
+        return Err(anyhow::anyhow!("The bad commit {} is an ancestor of a good commit", &bad[..8]));
+    }
+
+    let remaining = candidates.len() - 1;
+    let testable: Vec<&String> = candidates.keys().filter(|hash| **hash != bad && !skipped.contains(*hash)).collect();
+
+    if remaining == 0 {
+        let commit = &candidates[&bad];
+        println!("{} is the first bad commit", bad);
+        for line in signature_lines(commit) {
+            println!("{}", line);
+        }
+        println!();
+        for line in commit.message.lines() {
+            println!("    {}", line);
+        }
+        append_log(repo, &format!("# first bad commit: [{}] {}", &bad[..8], subject(commit)))?;
+        return Ok(Step::Done);
+    }
+    if testable.is_empty() {
+        println!("There are only 'skip'ped commits left to test.");
+        println!("The first bad commit could be any of:");
+        for hash in candidates.keys() {
+            println!("{}", hash);
+        }
+        return Ok(Step::Done);
+    }
+
+    let next = midpoint(&candidates, &testable);
+    let left = remaining / 2;
+    let steps = usize::BITS - (left + 1).leading_zeros() - 1;
+    println!("Bisecting: {} revision{} left to test after this (roughly {} step{})",
+        left, if left == 1 { "" } else { "s" }, steps, if steps == 1 { "" } else { "s" });
+
+    switch_worktree(repo, &next)?;
+    fs::write(&repo.head_file, format!("{}\n", next))?;
+    println!("[{}] {}", &next[..8], subject(&candidates[&next]));
+    Ok(Step::Testing)
+}
+
+/// Picks the candidate that splits the suspects most evenly: the one whose
+/// count of suspect ancestors is closest to half of all suspects.
+fn midpoint(candidates: &HashMap<String, Commit>, testable: &[&String]) -> String {
+    let total = candidates.len();
+    let mut best: Option<(usize, &String)> = None;
+
+    for &hash in testable {
+        let mut seen = HashSet::new();
+        let mut stack = vec![hash];
+        while let Some(current) = stack.pop() {
+            if seen.insert(current) {
+                stack.extend(candidates[current].parents.iter().filter(|parent| candidates.contains_key(*parent)));
+            }
+        }
+        let score = seen.len().min(total - seen.len());
+        if best.is_none_or(|(best_score, best_hash)| score > best_score || (score == best_score && hash < best_hash)) {
+            best = Some((score, hash));
+        }
+    }
+
+    best.map(|(_, hash)| hash.clone()).expect("at least one testable commit")
+}
+
+fn subject(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or("")
+}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use colored::*;
+use similar::{DiffOp, TextDiff};
+use crate::core::{Repository, Commit, Config};
+use crate::utils::diff::{diff_lines, text_content, DiffOptions};
+use crate::utils::pretty::format_date;
+
+/// Similarity above which a deleted file is taken as the source of a rename.
+const RENAME_THRESHOLD: f32 = 0.5;
+
+/// Lines still looking for their origin in one version of the file, as
+/// (line in the blamed file, line in this version).
+type PendingLines = Vec<(usize, usize)>;
+
+pub fn execute(file: String, revision: Option<String>, range: Option<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let revision = revision.unwrap_or_else(|| "HEAD".to_string());
+    let start = repo.resolve_revision(&revision)?;
+    let path = file.trim_start_matches("./").to_string();
+
+    let diff_options = DiffOptions::from_config(&Config::load(Some(&repo))?)?;
+    let mut history = History::new(&repo);
+    let content = history
+        .file_at(&start, &path)?
+        .ok_or_else(|| anyhow::anyhow!("No such path '{}' in {}", path, revision))?;
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let (first, last) = parse_range(range.as_deref(), lines.len())?;
+
+    // Walk from newest to oldest, handing each line down to the parent that
+    // already had it until some commit turns out to have introduced it
+    let mut origins: Vec<Option<(String, String)>> = vec![None; lines.len()];
+    let mut pending: HashMap<(String, String), PendingLines> = HashMap::new();
+    let mut queue: BinaryHeap<(DateTime<Utc>, String, String)> = BinaryHeap::new();
+    pending.insert((start.clone(), path.clone()), (first..last).map(|line| (line, line)).collect());
+    queue.push((history.commit(&start)?.time(), start, path.clone()));
+
+    while let Some((_, hash, current_path)) = queue.pop() {
+        let Some(mut remaining) = pending.remove(&(hash.clone(), current_path.clone())) else { continue };
+        let commit = history.commit(&hash)?.clone();
+        let content = history.file_at(&hash, &current_path)?.unwrap_or_default();
+
+        for parent in &commit.parents {
+            if remaining.is_empty() {
+                break;
+            }
+            let Some(parent_path) = history.source_path(&hash, parent, &current_path, &content)? else { continue };
+            let parent_content = history.file_at(parent, &parent_path)?.unwrap_or_default();
+
+            let mut unchanged: HashMap<usize, usize> = HashMap::new();
+            for op in &diff_lines(&parent_content, &content, &diff_options)?.ops {
+                if let DiffOp::Equal { old_index, new_index, len } = *op {
+                    unchanged.extend((0..len).map(|offset| (new_index + offset, old_index + offset)));
+                }
+            }
+
+            let (inherited, kept): (PendingLines, PendingLines) =
+                remaining.into_iter().partition(|(_, line)| unchanged.contains_key(line));
+            remaining = kept;
+            if inherited.is_empty() {
+                continue;
+            }
+
+            let key = (parent.clone(), parent_path.clone());
+            if !pending.contains_key(&key) {
+                queue.push((history.commit(parent)?.time(), parent.clone(), parent_path));
+            }
+            pending
+                .entry(key)
+                .or_default()
+                .extend(inherited.into_iter().map(|(line, current)| (line, unchanged[&current])));
+        }
+
+        for (line, _) in remaining {
+            origins[line] = Some((hash.clone(), current_path.clone()));
+        }
+    }
+
+    let show_paths = origins.iter().flatten().any(|(_, origin_path)| *origin_path != path);
+    let mut rows = Vec::new();
+    for origin in &origins[first..last] {
+        let (hash, origin_path) = origin.clone().expect("every line has an origin");
+        let commit = history.commit(&hash)?;
+        rows.push((hash, origin_path, commit.author.name.clone(), format_date(&commit.author.when)));
+    }
+    let author_width = rows.iter().map(|row| row.2.chars().count()).max().unwrap_or(0);
+    let path_width = rows.iter().map(|row| row.1.chars().count()).max().unwrap_or(0);
+    let number_width = last.to_string().len();
+
+    for (line, (hash, origin_path, author, date)) in (first..last).zip(rows) {
+        let path_column = if show_paths { format!(" {:<width$}", origin_path, width = path_width) } else { String::new() };
+        println!(
+            "{}{} ({:<author_width$} {} {:>number_width$}) {}",
+            hash[..8].yellow(),
+            path_column,
+            author,
+            date,
+            line + 1,
+            lines[line].trim_end_matches(['\n', '\r']),
+        );
+    }
+
+    Ok(())
+}
+
+/// Parses `-L start,end` or `-L start,+count` into a zero-based half-open range.
+fn parse_range(range: Option<&str>, line_count: usize) -> Result<(usize, usize)> {
+    let Some(range) = range else { return Ok((0, line_count)) };
+    let invalid = || anyhow::anyhow!("Invalid line range '{}'", range);
+
+    let (start, end) = range.split_once(',').unwrap_or((range, ""));
+    let start: usize = if start.is_empty() { 1 } else { start.trim().parse().map_err(|_| invalid())? };
+    let end: usize = match end.trim() {
+        "" => line_count,
+        end => match end.strip_prefix('+') {
+            Some(count) => start + count.parse::<usize>().map_err(|_| invalid())?.saturating_sub(1),
+            None => end.parse().map_err(|_| invalid())?,
+        },
+    };
+
+    if start == 0 || start > end || end > line_count {
+        return Err(anyhow::anyhow!("Line range {} is outside the file's {} lines", range, line_count));
+    }
+    Ok((start - 1, end))
+}
+
+/// Caches the commits and trees a blame walk keeps revisiting.
+struct History<'a> {
+    repo: &'a Repository,
+    commits: HashMap<String, Commit>,
+    trees: HashMap<String, BTreeMap<String, String>>,
+}
+
+impl<'a> History<'a> {
+    fn new(repo: &'a Repository) -> Self {
+        Self { repo, commits: HashMap::new(), trees: HashMap::new() }
+    }
+
+    fn commit(&mut self, hash: &str) -> Result<&Commit> {
+        if !self.commits.contains_key(hash) {
+            self.commits.insert(hash.to_string(), self.repo.load_commit(hash)?);
+        }
+        Ok(&self.commits[hash])
+    }
+
+    fn files(&mut self, hash: &str) -> Result<&BTreeMap<String, String>> {
+        let tree = self.commit(hash)?.tree.clone();
+        if !self.trees.contains_key(&tree) {
+            let files = self.repo.flatten_tree(&tree)?;
+            self.trees.insert(tree.clone(), files);
+        }
+        Ok(&self.trees[&tree])
+    }
+
+    fn file_at(&mut self, hash: &str, path: &str) -> Result<Option<String>> {
+        let Some(blob_hash) = self.files(hash)?.get(path).cloned() else { return Ok(None) };
+        let blob_obj = self.repo.load_object(&blob_hash)?;
+        let content = blob_obj.as_blob().map(|blob| blob.content.clone()).unwrap_or_default();
+        match text_content(&content) {
+            Some(text) => Ok(Some(text.to_string())),
+            None => Err(anyhow::anyhow!("Cannot blame binary file '{}'", path)),
+        }
+    }
+
+    /// Finds where `path` lived in `parent`: the same path, or else the most
+    /// similar file that `child` no longer has.
+    fn source_path(&mut self, child: &str, parent: &str, path: &str, content: &str) -> Result<Option<String>> {
+        let parent_files = self.files(parent)?.clone();
+        if parent_files.contains_key(path) {
+            return Ok(Some(path.to_string()));
+        }
+        let child_files = self.files(child)?;
+        let blob_hash = child_files.get(path).cloned();
+        let candidates: Vec<(String, String)> = parent_files
+            .into_iter()
+            .filter(|(candidate, _)| !child_files.contains_key(candidate))
+            .collect();
+
+        if let Some((candidate, _)) = candidates.iter().find(|(_, hash)| Some(hash) == blob_hash.as_ref()) {
+            return Ok(Some(candidate.clone()));
+        }
+
+        let mut best: Option<(f32, String)> = None;
+        for (candidate, _) in candidates {
+            let Ok(Some(old_content)) = self.file_at(parent, &candidate) else { continue };
+            let ratio = TextDiff::from_lines(old_content.as_str(), content).ratio();
+            if ratio >= RENAME_THRESHOLD && best.as_ref().is_none_or(|(best_ratio, _)| ratio > *best_ratio) {
+                best = Some((ratio, candidate));
+            }
+        }
+        Ok(best.map(|(_, candidate)| candidate))
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::Result;
+use crate::core::Repository;
+use crate::core::attributes::Attributes;
+
+/// Prints `path: attribute: value` for each of `names` on each path, or
+/// every specified attribute with `all`. Values are `set`, `unset`,
+/// `unspecified` or the assigned string.
+pub fn execute(names: Vec<String>, paths: Vec<String>, all: bool) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let attributes = Attributes::load(&repo)?;
+
+    for path in paths {
+        let path = path.trim_start_matches("./").to_string();
+        let found = attributes.get(&path)?;
+        if all {
+            for (name, value) in found.iter() {
+                println!("{}: {}: {}", path, name, value);
+            }
+            continue;
+        }
+        for name in &names {
+            match found.get(name) {
+                Some(value) => println!("{}: {}: {}", path, name, value),
+                None => println!("{}: {}: unspecified", path, name),
+            }
+        }
+    }
+    Ok(())
+}
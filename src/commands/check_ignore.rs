@@ -0,0 +1,29 @@
+use std::path::Path;
+use anyhow::Result;
+use crate::core::Repository;
+use crate::utils::files::IgnoreRules;
+
+/// Prints the paths that are ignored; with `verbose`, prefixed by the
+/// matching rule as `source:line:pattern`. `non_matching` also lists paths
+/// no rule matched. Returns whether any path was ignored.
+pub fn execute(paths: Vec<String>, verbose: bool, non_matching: bool) -> Result<bool> {
+    let repo = Repository::find_repository()?;
+    let rules = IgnoreRules::load(&repo)?;
+
+    let mut any_ignored = false;
+    for path in paths {
+        let found = rules.explain(Path::new(&path))?;
+        any_ignored |= found.as_ref().is_some_and(|found| found.is_ignored());
+        match found {
+            // Without -v a re-included path counts as not ignored
+            Some(found) if verbose => {
+                let source = found.source.strip_prefix(&repo.root).unwrap_or(&found.source);
+                println!("{}:{}:{}\t{}", source.display(), found.line, found.pattern, path);
+            }
+            Some(found) if found.is_ignored() => println!("{}", path),
+            None if verbose && non_matching => println!("::\t{}", path),
+            _ => {}
+        }
+    }
+    Ok(any_ignored)
+}
@@ -1,6 +1,8 @@
 use std::fs;
 use anyhow::Result;
-use crate::core::Repository;
+use crate::core::{hooks, Repository};
+use crate::core::reflog::ZERO_HASH;
+use crate::core::worktree::checkout_tree;
 
 pub fn execute(target: String, create_branch: bool) -> Result<()> {
     let repo = Repository::find_repository()?;
@@ -13,25 +15,42 @@ pub fn execute(target: String, create_branch: bool) -> Result<()> {
         repo.create_branch(&target, &head_commit)?;
         repo.checkout_branch(&target)?;
         println!("Switched to a new branch '{}'", target);
+        hooks::run_post(&repo, "post-checkout", &[&head_commit, &head_commit, "1"]);
+        return Ok(());
+    }
+
+    // Check if target is a branch name
+    let branches = repo.list_branches()?;
+    let is_branch = branches.contains(&target);
+    let target_commit = if is_branch {
+        repo.resolve_revision(&target)?
     } else {
-        // Check if target is a branch name
-        let branches = repo.list_branches()?;
-        if branches.contains(&target) {
-            repo.checkout_branch(&target)?;
-            println!("Switched to branch '{}'", target);
-        } else {
-            // Try to checkout specific commit
-            if repo.load_object(&target).is_ok() {
-                fs::write(&repo.head_file, format!("{}\n", target))?;
-                println!("HEAD is now at {} (detached)", &target[..8]);
-            } else {
-                return Err(anyhow::anyhow!("Branch or commit '{}' not found", target));
-            }
-        }
+        repo.resolve_revision(&target)
+            .map_err(|_| anyhow::anyhow!("Branch or commit '{}' not found", target))?
+    };
+
+    // Update working directory files to match the checked out commit
+    let previous = repo.get_head()?.unwrap_or_else(|| ZERO_HASH.to_string());
+    switch_worktree(&repo, &target_commit)?;
+
+    if is_branch {
+        repo.checkout_branch(&target)?;
+        println!("Switched to branch '{}'", target);
+    } else {
+        fs::write(&repo.head_file, format!("{}\n", target_commit))?;
+        println!("HEAD is now at {} (detached)", &target_commit[..8]);
     }
 
-    // TODO: Update working directory files to match the checked out commit
-    // This would involve reading the tree from the target commit and updating files
-    
+    hooks::run_post(&repo, "post-checkout", &[&previous, &target_commit, "1"]);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Replaces the working tree files of the current HEAD with those of `commit`.
+pub fn switch_worktree(repo: &Repository, commit: &str) -> Result<()> {
+    let current_tree = match repo.get_head()? {
+        Some(head) => Some(repo.load_commit(&head)?.tree),
+        None => None,
+    };
+    let target_tree = repo.load_commit(commit)?.tree;
+    checkout_tree(repo, current_tree.as_deref(), &target_tree)
+}
@@ -0,0 +1,17 @@
+use anyhow::Result;
+use crate::commands::sequencer::{self, Action, SequenceOptions};
+
+pub fn execute(
+    revisions: Vec<String>,
+    options: SequenceOptions,
+    resume: bool,
+    abort: bool,
+) -> Result<()> {
+    if resume {
+        sequencer::resume(Action::Pick)
+    } else if abort {
+        sequencer::abort(Action::Pick)
+    } else {
+        sequencer::start(Action::Pick, &revisions, options)
+    }
+}
@@ -1,43 +1,73 @@
 use std::fs;
 use anyhow::Result;
-use crate::core::{Repository, Object, Commit, Tree, Blob};
+use crate::core::{Repository, Object, Commit, Config, Signature};
+use crate::core::{hooks, reflog};
+use crate::core::worktree::write_index_tree;
+use crate::utils::editor::{edit_file, strip_comments};
 
-pub fn execute(message: String, author: Option<String>) -> Result<()> {
+#[derive(Debug, Default)]
+pub struct CommitOptions {
+    pub message: Option<String>,
+    pub author: Option<String>,
+    /// Replace HEAD instead of adding a child to it
+    pub amend: bool,
+    /// Edit the message even though one was given or reused
+    pub edit: bool,
+    /// Mark the commit to be folded into this revision by `rebase --autosquash`
+    pub fixup: Option<String>,
+    /// Like `fixup`, but keep this commit's message when folding
+    pub squash: Option<String>,
+    /// Skip the pre-commit and commit-msg hooks
+    pub no_verify: bool,
+}
+
+pub fn execute(options: CommitOptions) -> Result<()> {
     let repo = Repository::find_repository()?;
-    let index = repo.load_index()?;
+    let config = Config::load(Some(&repo))?;
+    let head = repo.get_head()?;
 
-    if index.entries.is_empty() {
+    if repo.load_index()?.entries.is_empty() && !options.amend {
         return Err(anyhow::anyhow!("No changes staged for commit"));
     }
+    if !options.no_verify {
+        hooks::run(&repo, "pre-commit", &[])?;
+    }
+    // The hook may have staged more changes
+    let index = repo.load_index()?;
+
+    // Create tree from HEAD plus the staged changes
+    let tree_hash = write_index_tree(&repo, &index)?;
 
-    // Create tree from index
-    let mut tree = Tree::new();
-    for (path, entry) in &index.entries {
-        let file_path = repo.root.join(path);
-        let content = fs::read(&file_path)?;
-        let blob = Blob::new(content);
-        let blob_obj = Object::Blob(blob);
-        let blob_hash = repo.store_object(&blob_obj)?;
-        
-        tree.add_entry(
-            path.to_string_lossy().to_string(),
-            blob_hash,
-            true,
-        );
-    }
-
-    let tree_obj = Object::Tree(tree);
-    let tree_hash = repo.store_object(&tree_obj)?;
-
-    // Get parent commit
-    let parent_commits = match repo.get_head()? {
-        Some(head_commit) => vec![head_commit],
-        None => vec![],
+    let (mut parent_commits, previous) = match (&head, options.amend) {
+        (Some(head), true) => {
+            let previous = repo.load_commit(head)?;
+            (previous.parents.clone(), Some(previous))
+        }
+        (None, true) => return Err(anyhow::anyhow!("You have nothing to amend")),
+        (Some(head), false) => (vec![head.clone()], None),
+        (None, false) => (vec![], None),
+    };
+    // Concluding a merge that stopped on conflicts
+    let merge_head = repo.nvcs_dir.join("MERGE_HEAD");
+    let merging = merge_head.exists();
+    if merging {
+        parent_commits.push(fs::read_to_string(&merge_head)?.trim().to_string());
+    }
+
+    let mut message = commit_message(&repo, &options, previous.as_ref())?;
+    if !options.no_verify {
+        message = run_commit_msg_hook(&repo, &message)?;
+    }
+    // An amended commit keeps its author unless --author replaces it
+    let default_author = config.author()?;
+    let author = match (&options.author, previous) {
+        (Some(identity), _) => Signature::parse(identity, default_author.when),
+        (None, Some(previous)) => previous.author,
+        (None, None) => default_author,
     };
 
     // Create commit
-    let author_name = author.unwrap_or_else(|| "Unknown".to_string());
-    let commit = Commit::new(tree_hash, parent_commits, author_name, message);
+    let commit = Commit::new(tree_hash, parent_commits, author, config.committer()?, message);
     let commit_obj = Object::Commit(commit.clone());
     let commit_hash = repo.store_object(&commit_obj)?;
 
@@ -48,11 +78,82 @@ pub fn execute(message: String, author: Option<String>) -> Result<()> {
         fs::write(&repo.head_file, format!("{}\n", commit_hash))?;
     }
 
+    // Keep the replaced commit reachable through the reflog
+    let subject = commit.message.lines().next().unwrap_or("");
+    let reflog_message = match (&head, options.amend) {
+        (_, true) => format!("commit (amend): {}", subject),
+        (None, _) => format!("commit (initial): {}", subject),
+        _ if merging => format!("commit (merge): {}", subject),
+        _ => format!("commit: {}", subject),
+    };
+    reflog::append(&repo, "HEAD", head.as_deref(), &commit_hash, &reflog_message)?;
+    if let Some(current_branch) = repo.get_current_branch()? {
+        let branch_ref = format!("refs/heads/{}", current_branch);
+        reflog::append(&repo, &branch_ref, head.as_deref(), &commit_hash, &reflog_message)?;
+    }
+
     // Clear index
-    let mut empty_index = crate::core::Index::new();
+    let empty_index = crate::core::Index::new();
     repo.save_index(&empty_index)?;
+    if merging {
+        fs::remove_file(&merge_head)?;
+    }
 
     println!("Committed {} files", index.entries.len());
     println!("Commit hash: {}", commit.short_hash());
+    hooks::run_post(&repo, "post-commit", &[]);
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Lets the commit-msg hook check or rewrite the message through the file
+/// it is given.
+fn run_commit_msg_hook(repo: &Repository, message: &str) -> Result<String> {
+    let file = repo.nvcs_dir.join("COMMIT_EDITMSG");
+    fs::write(&file, format!("{}\n", message.trim_end()))?;
+    hooks::run(repo, "commit-msg", &[&file.to_string_lossy()])?;
+    let message = fs::read_to_string(&file)?.trim_end().to_string();
+    if message.is_empty() {
+        return Err(anyhow::anyhow!("Aborting commit due to empty commit message"));
+    }
+    Ok(message)
+}
+
+/// Works out the message from `-m`, the amended commit or the fixup/squash
+/// target, opening the editor when none was given or `--edit` asks for it.
+fn commit_message(repo: &Repository, options: &CommitOptions, previous: Option<&Commit>) -> Result<String> {
+    let marker = match (&options.fixup, &options.squash) {
+        (Some(revision), _) => Some(("fixup!", revision)),
+        (_, Some(revision)) => Some(("squash!", revision)),
+        _ => None,
+    };
+    let (message, edit) = match marker {
+        Some((marker, revision)) => {
+            let target = repo.load_commit(&repo.resolve_revision(revision)?)?;
+            let subject = format!("{} {}", marker, target.message.lines().next().unwrap_or(""));
+            match &options.message {
+                Some(message) => (format!("{}\n\n{}", subject, message), options.edit),
+                None => (subject, options.edit),
+            }
+        }
+        None => match (&options.message, previous) {
+            (Some(message), _) => (message.clone(), options.edit),
+            (None, Some(previous)) => (previous.message.clone(), options.edit),
+            (None, None) => (String::new(), true),
+        },
+    };
+    if !edit {
+        return Ok(message);
+    }
+
+    let file = repo.nvcs_dir.join("COMMIT_EDITMSG");
+    fs::write(&file, format!(
+        "{}\n\n# Please enter the commit message for your changes. Lines starting\n# with '#' will be ignored, and an empty message aborts the commit.\n",
+        message.trim_end()
+    ))?;
+    edit_file(&file)?;
+    let edited = strip_comments(&fs::read_to_string(&file)?);
+    if edited.is_empty() {
+        return Err(anyhow::anyhow!("Aborting commit due to empty commit message"));
+    }
+    Ok(edited)
+}
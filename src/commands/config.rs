@@ -0,0 +1,64 @@
+use anyhow::Result;
+use crate::core::{Repository, Config};
+use crate::core::config::{normalize_key, ConfigFile, ConfigScope};
+
+/// What `nvcs config` was asked to do.
+#[derive(Debug)]
+pub enum ConfigAction {
+    Get(String),
+    Set(String, String),
+    Unset(String),
+    List,
+}
+
+/// Runs `action` against one scope's file, or against the layered settings
+/// when reading without a scope. Writes default to the local scope.
+pub fn execute(action: ConfigAction, scope: Option<ConfigScope>, show_scope: bool) -> Result<()> {
+    let repo = Repository::find_repository().ok();
+
+    match action {
+        ConfigAction::Get(key) => {
+            normalize_key(&key)?;
+            let value = match scope {
+                Some(scope) => scope_file(repo.as_ref(), scope)?.get(&key).map(str::to_string),
+                None => Config::load(repo.as_ref())?.get(&key).map(str::to_string),
+            };
+            let value = value.ok_or_else(|| anyhow::anyhow!("{} is not set", key))?;
+            println!("{}", value);
+        }
+        ConfigAction::Set(key, value) => {
+            let mut file = scope_file(repo.as_ref(), scope.unwrap_or(ConfigScope::Local))?;
+            file.set(&key, &value)?;
+            file.save()?;
+        }
+        ConfigAction::Unset(key) => {
+            let mut file = scope_file(repo.as_ref(), scope.unwrap_or(ConfigScope::Local))?;
+            if !file.unset(&key)? {
+                return Err(anyhow::anyhow!("{} is not set in {}", key, file.path.display()));
+            }
+            file.save()?;
+        }
+        ConfigAction::List => {
+            let config = Config::load(repo.as_ref())?;
+            for (entry_scope, key, value) in config.entries() {
+                if scope.is_some_and(|scope| scope != entry_scope) {
+                    continue;
+                }
+                if show_scope {
+                    println!("{}\t{}={}", entry_scope.name(), key, value);
+                } else {
+                    println!("{}={}", key, value);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn scope_file(repo: Option<&Repository>, scope: ConfigScope) -> Result<ConfigFile> {
+    let path = scope.path(repo).ok_or_else(|| match scope {
+        ConfigScope::Local => anyhow::anyhow!("--local can only be used inside a repository"),
+        _ => anyhow::anyhow!("Cannot find the {} config file; is $HOME set?", scope.name()),
+    })?;
+    ConfigFile::load(&path)
+}
@@ -1,25 +1,39 @@
-use std::fs;
 use anyhow::Result;
-use crate::core::Repository;
-use crate::utils::diff::print_diff;
+use colored::Colorize;
+use crate::core::{Repository, FileMode};
+use crate::core::attributes::Attributes;
+use crate::utils::diff::{format_summary, DiffOptions, FileStat, SummaryFormat};
 
-pub fn execute(staged: bool, files: Vec<String>) -> Result<()> {
+/// A file to diff: path, status letter, old content, new content and the
+/// old and new modes when the mode changed.
+type FileDiff = (String, char, Vec<u8>, Vec<u8>, Option<(FileMode, FileMode)>);
+
+pub fn execute(
+    staged: bool,
+    files: Vec<String>,
+    summary: Option<SummaryFormat>,
+    options: DiffOptions,
+) -> Result<()> {
     let repo = Repository::find_repository()?;
-    let index = repo.load_index()?;
+    let mut index = repo.load_index()?;
+    // Working files are diffed as they would be stored
+    let attributes = Attributes::load(&repo)?;
+    let mut diffs: Vec<FileDiff> = Vec::new();
 
     if staged {
         // Show diff between HEAD and staged files
         let head_commit = match repo.get_head()? {
             Some(commit) => commit,
             None => {
-                println!("No commits yet - showing all staged files as new");
-                for (path, _) in &index.entries {
-                    let file_path = repo.root.join(path);
-                    if let Ok(content) = fs::read_to_string(&file_path) {
-                        print_diff("", &content, &path.to_string_lossy());
+                if summary.is_none() {
+                    println!("No commits yet - showing all staged files as new");
+                }
+                for (path, _) in index.entries.iter().filter(|(_, entry)| !entry.removed) {
+                    if let Ok(content) = attributes.read_file(&path.to_string_lossy()) {
+                        diffs.push((path.to_string_lossy().to_string(), 'A', Vec::new(), content, None));
                     }
                 }
-                return Ok(());
+                return print_diffs(&attributes, diffs, summary, &options);
             }
         };
 
@@ -29,53 +43,68 @@ pub fn execute(staged: bool, files: Vec<String>) -> Result<()> {
         let tree = tree_obj.as_tree().unwrap();
 
         for (path, entry) in &index.entries {
-            let file_path = repo.root.join(path);
-            let new_content = fs::read_to_string(&file_path).unwrap_or_default();
-            
+            let new_content = attributes.read_file(&path.to_string_lossy()).unwrap_or_default();
+
             // Find corresponding file in HEAD tree
             let path_str = path.to_string_lossy();
             if let Some(tree_entry) = tree.entries.get(&path_str.to_string()) {
                 let blob_obj = repo.load_object(&tree_entry.hash)?;
                 let blob = blob_obj.as_blob().unwrap();
-                let old_content = blob.to_string().unwrap_or_default();
-                
-                if old_content != new_content {
-                    print_diff(&old_content, &new_content, &path_str);
+                let old_content = blob.content.clone();
+
+                let modes = mode_change(tree_entry.mode, entry.mode);
+                if entry.removed {
+                    diffs.push((path_str.to_string(), 'D', old_content, Vec::new(), None));
+                } else if old_content != new_content || modes.is_some() {
+                    diffs.push((path_str.to_string(), 'M', old_content, new_content, modes));
                 }
-            } else {
+            } else if !entry.removed {
                 // New file
-                print_diff("", &new_content, &path_str);
+                diffs.push((path_str.to_string(), 'A', Vec::new(), new_content, None));
             }
         }
     } else {
         // Show diff between staged and working directory
         if files.is_empty() {
-            // Show all modified files
-            for (path, entry) in &index.entries {
-                let file_path = repo.root.join(path);
-                if let Ok(current_content) = fs::read_to_string(&file_path) {
+            // Show all modified files, skipping those whose stat data shows
+            // them untouched since they were staged
+            let paths: Vec<_> = index.entries.iter()
+                .filter(|(_, entry)| !entry.removed)
+                .map(|(path, _)| path.clone())
+                .collect();
+            for path in &paths {
+                if matches!(index.is_up_to_date(path, &repo.root, &attributes), Ok(true)) {
+                    continue;
+                }
+                let entry = &index.entries[path];
+                if let Ok(current_content) = attributes.read_file(&path.to_string_lossy()) {
                     // Get staged content
                     let blob_obj = repo.load_object(&entry.hash)?;
                     let blob = blob_obj.as_blob().unwrap();
-                    let staged_content = blob.to_string().unwrap_or_default();
-                    
-                    if staged_content != current_content {
-                        print_diff(&staged_content, &current_content, &path.to_string_lossy());
+                    let staged_content = blob.content.clone();
+
+                    let modes = mode_change(entry.mode, FileMode::of(&repo.root.join(path))?);
+                    if staged_content != current_content || modes.is_some() {
+                        diffs.push((path.to_string_lossy().to_string(), 'M', staged_content, current_content, modes));
                     }
                 }
             }
+            if index.is_refreshed() {
+                // Diff still works where the index cannot be written
+                let _ = repo.save_index(&index);
+            }
         } else {
             // Show diff for specific files
             for file_str in files {
                 let path = std::path::Path::new(&file_str);
-                if let Some(entry) = index.entries.get(path) {
-                    let file_path = repo.root.join(path);
-                    if let Ok(current_content) = fs::read_to_string(&file_path) {
+                if let Some(entry) = index.entries.get(path).filter(|entry| !entry.removed) {
+                    if let Ok(current_content) = attributes.read_file(&path.to_string_lossy()) {
                         let blob_obj = repo.load_object(&entry.hash)?;
                         let blob = blob_obj.as_blob().unwrap();
-                        let staged_content = blob.to_string().unwrap_or_default();
-                        
-                        print_diff(&staged_content, &current_content, &file_str);
+                        let staged_content = blob.content.clone();
+
+                        let modes = mode_change(entry.mode, FileMode::of(&repo.root.join(path))?);
+                        diffs.push((file_str, 'M', staged_content, current_content, modes));
                     }
                 } else {
                     println!("File not staged: {}", file_str);
@@ -84,5 +113,32 @@ pub fn execute(staged: bool, files: Vec<String>) -> Result<()> {
         }
     }
 
+    print_diffs(&attributes, diffs, summary, &options)
+}
+
+fn print_diffs(attributes: &Attributes, mut diffs: Vec<FileDiff>, summary: Option<SummaryFormat>, options: &DiffOptions) -> Result<()> {
+    diffs.sort_by(|a, b| a.0.cmp(&b.0));
+
+    match summary {
+        Some(format) => {
+            let stats = diffs
+                .iter()
+                .map(|(path, status, old, new, _)| attributes.file_stat(path, *status, old, new, options))
+                .collect::<Result<Vec<FileStat>>>()?;
+            print!("{}", format_summary(&stats, format));
+        }
+        None => {
+            for (path, _, old, new, modes) in &diffs {
+                if let Some((old_mode, new_mode)) = modes {
+                    println!("{} {} => {} {}", "mode change".cyan(), old_mode.octal(), new_mode.octal(), path);
+                }
+                attributes.print_diff(path, old, new, options)?;
+            }
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+fn mode_change(old: FileMode, new: FileMode) -> Option<(FileMode, FileMode)> {
+    (old != new).then_some((old, new))
+}
@@ -1,7 +1,8 @@
 use anyhow::Result;
-use crate::core::Repository;
+use crate::core::{Repository, Config};
 
 pub fn execute() -> Result<()> {
     let repo = Repository::new(".");
-    repo.init()
+    let config = Config::load(None)?;
+    repo.init(config.get("init.defaultbranch").unwrap_or("main"))
 }
\ No newline at end of file
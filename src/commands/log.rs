@@ -1,51 +1,259 @@
-use anyhow::Result;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use colored::*;
-use crate::core::Repository;
+use regex::Regex;
+use crate::core::{Repository, Commit, Config};
+use crate::core::revwalk::{RevWalk, WalkOrder};
+use crate::utils::diff::{format_summary, DiffOptions, SummaryFormat};
+use crate::utils::date::parse_date;
+use crate::utils::graph::GraphRenderer;
+use crate::utils::pretty::{
+    commit_file_stats, commit_json, decoration_names, expand_template, format_decoration, ref_decorations,
+    signature_lines, PrettyFormat,
+};
 
-pub fn execute(count: Option<usize>, oneline: bool) -> Result<()> {
+pub struct LogOptions {
+    pub revisions: Vec<String>,
+    pub count: Option<usize>,
+    pub format: PrettyFormat,
+    pub summary: Option<SummaryFormat>,
+    pub order: WalkOrder,
+    pub first_parent: bool,
+    pub graph: bool,
+    pub decorate: bool,
+    pub author: Option<String>,
+    pub grep: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub paths: Vec<String>,
+}
+
+/// Criteria a commit must meet to be listed.
+struct CommitFilter {
+    author: Option<Regex>,
+    grep: Option<Regex>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    paths: Vec<String>,
+    /// Flattened trees by tree hash, shared between parent and child checks
+    trees: HashMap<String, BTreeMap<String, String>>,
+}
+
+pub fn execute(options: LogOptions) -> Result<()> {
+    if options.graph && options.format == PrettyFormat::Json {
+        return Err(anyhow::anyhow!("--graph cannot be used with --format=json"));
+    }
     let repo = Repository::find_repository()?;
-    
-    let mut current_commit = match repo.get_head()? {
-        Some(commit) => commit,
-        None => {
-            println!("No commits yet");
-            return Ok(());
+    let diff_options = DiffOptions::from_config(&Config::load(Some(&repo))?)?;
+
+    let mut walk = RevWalk::new(&repo);
+    walk.order = options.order;
+    walk.first_parent = options.first_parent;
+
+    if options.revisions.iter().all(|revision| revision.starts_with('^')) {
+        match repo.get_head()? {
+            Some(commit) => walk.push(commit),
+            None => {
+                println!("No commits yet");
+                return Ok(());
+            }
         }
+    }
+    for revision in &options.revisions {
+        match revision.strip_prefix('^') {
+            Some(excluded) => walk.hide(repo.resolve_revision(excluded)?),
+            None => walk.push(repo.resolve_revision(revision)?),
+        }
+    }
+
+    let now = Utc::now();
+    let mut filter = CommitFilter {
+        author: options.author.as_deref().map(Regex::new).transpose().context("Invalid --author pattern")?,
+        grep: options.grep.as_deref().map(Regex::new).transpose().context("Invalid --grep pattern")?,
+        since: options.since.as_deref().map(|date| parse_date(date, now)).transpose()?,
+        until: options.until.as_deref().map(|date| parse_date(date, now)).transpose()?,
+        paths: options.paths.iter().map(|path| normalize_path(path)).collect(),
+        trees: HashMap::new(),
     };
 
-    let max_count = count.unwrap_or(usize::MAX);
-    let mut shown = 0;
+    let walked = walk.commits()?;
+    let mut selected = Vec::new();
+    for (hash, commit) in &walked {
+        if filter.matches(&repo, commit)? {
+            selected.push((hash, commit));
+        }
+    }
+
+    let max_count = options.count.unwrap_or(usize::MAX);
+    // Templates can ask for ref names even without --decorate
+    let decorations = if options.decorate || matches!(options.format, PrettyFormat::Template(_)) {
+        ref_decorations(&repo)?
+    } else {
+        HashMap::new()
+    };
+    let mut graph = GraphRenderer::new();
+    let shown: HashSet<&String> = selected.iter().map(|(hash, _)| *hash).collect();
+    let walked: HashMap<&String, &Commit> = walked.iter().map(|(hash, commit)| (hash, commit)).collect();
 
-    while shown < max_count {
-        let commit_obj = repo.load_object(&current_commit)?;
-        let commit = commit_obj.as_commit().unwrap();
+    for (hash, commit) in selected.into_iter().take(max_count) {
+        if options.format == PrettyFormat::Json {
+            println!("{}", commit_json(&repo, hash, commit)?);
+            continue;
+        }
 
-        if oneline {
-            println!(
-                "{} {}",
-                commit.short_hash().yellow(),
-                commit.message.lines().next().unwrap_or("")
-            );
+        let parents = if options.graph {
+            visible_parents(commit, &walked, &shown, options.first_parent)
         } else {
-            println!("{} {}", "commit".yellow(), current_commit);
-            println!("{} {}", "Author:".bright_white(), commit.author);
-            println!("{} {}", "Date:".bright_white(), commit.timestamp.format("%Y-%m-%d %H:%M:%S UTC"));
-            println!();
-            for line in commit.message.lines() {
-                println!("    {}", line);
+            Vec::new()
+        };
+        let rows = graph.next(hash, &parents);
+        let (prefix, padding) = if options.graph {
+            for row in &rows.before {
+                println!("{}", row);
             }
-            println!();
-        }
+            (rows.commit, rows.padding)
+        } else {
+            (String::new(), String::new())
+        };
 
-        shown += 1;
+        let labels = decorations.get(hash).map(Vec::as_slice).unwrap_or_default();
+        let decoration = if options.decorate { format_decoration(labels) } else { String::new() };
+        let mut lines = Vec::new();
+        match &options.format {
+            PrettyFormat::Oneline => {
+                lines.push(format!(
+                    "{}{} {}",
+                    hash[..8].yellow(),
+                    decoration,
+                    commit.message.lines().next().unwrap_or("")
+                ));
+            }
+            PrettyFormat::Template(template) => {
+                let expanded = expand_template(template, hash, commit, &decoration_names(labels));
+                lines.extend(expanded.split('\n').map(str::to_string));
+            }
+            _ => {
+                lines.push(format!("{} {}{}", "commit".yellow(), hash, decoration));
+                if commit.is_merge() {
+                    lines.push(format!("{} {}", "Merge:".bright_white(), commit.parents.iter().map(|parent| &parent[..8]).collect::<Vec<_>>().join(" ")));
+                }
+                lines.extend(signature_lines(commit));
+                lines.push(String::new());
+                for line in commit.message.lines() {
+                    lines.push(format!("    {}", line));
+                }
+                lines.push(String::new());
+            }
+        }
+        if let Some(format) = options.summary {
+            let stats = commit_file_stats(&repo, commit, &diff_options)?;
+            if !stats.is_empty() {
+                lines.extend(format_summary(&stats, format).lines().map(str::to_string));
+                if options.format == PrettyFormat::Medium {
+                    lines.push(String::new());
+                }
+            }
+        }
 
-        // Move to parent commit
-        if let Some(parent) = commit.parents.first() {
-            current_commit = parent.clone();
-        } else {
-            break;
+        for (number, line) in lines.iter().enumerate() {
+            let line_prefix = if number == 0 { &prefix } else { &padding };
+            if line_prefix.is_empty() || line.is_empty() {
+                println!("{}{}", line_prefix, line);
+            } else {
+                println!("{} {}", line_prefix, line);
+            }
+        }
+        if options.graph {
+            for row in &rows.after {
+                println!("{}", row);
+            }
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+impl CommitFilter {
+    fn matches(&mut self, repo: &Repository, commit: &Commit) -> Result<bool> {
+        if self.author.as_ref().is_some_and(|author| !author.is_match(&commit.author.identity()))
+            || self.grep.as_ref().is_some_and(|grep| !grep.is_match(&commit.message))
+            || self.since.is_some_and(|since| commit.time() < since)
+            || self.until.is_some_and(|until| commit.time() > until)
+        {
+            return Ok(false);
+        }
+        if self.paths.is_empty() {
+            return Ok(true);
+        }
+
+        // A commit touches the paths unless some parent has the same
+        // entries for them; a root commit touches whatever it contains
+        let entries = self.path_entries(repo, &commit.tree)?;
+        if commit.parents.is_empty() {
+            return Ok(!entries.is_empty());
+        }
+        for parent in &commit.parents {
+            let parent_tree = repo.load_commit(parent)?.tree;
+            if self.path_entries(repo, &parent_tree)? == entries {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the files of a tree that fall under the filtered paths.
+    fn path_entries(&mut self, repo: &Repository, tree: &str) -> Result<Vec<(String, String)>> {
+        if !self.trees.contains_key(tree) {
+            self.trees.insert(tree.to_string(), repo.flatten_tree(tree)?);
+        }
+        Ok(self.trees[tree]
+            .iter()
+            .filter(|(path, _)| {
+                self.paths.iter().any(|filter| {
+                    filter.is_empty()
+                        || path.as_str() == filter
+                        || path.strip_prefix(filter.as_str()).is_some_and(|rest| rest.starts_with('/'))
+                })
+            })
+            .map(|(path, hash)| (path.clone(), hash.clone()))
+            .collect())
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    if path == "." { String::new() } else { path.to_string() }
+}
+
+/// Rewrites a commit's parents to the nearest listed ancestors, so the graph
+/// joins up across commits that were filtered out.
+fn visible_parents(
+    commit: &Commit,
+    walked: &HashMap<&String, &Commit>,
+    shown: &HashSet<&String>,
+    first_parent: bool,
+) -> Vec<String> {
+    let direct = |commit: &Commit| -> Vec<String> {
+        if first_parent {
+            commit.parents.iter().take(1).cloned().collect()
+        } else {
+            commit.parents.clone()
+        }
+    };
+
+    let mut parents = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<String> = direct(commit).into_iter().rev().collect();
+    while let Some(hash) = stack.pop() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        if shown.contains(&hash) {
+            parents.push(hash);
+        } else if let Some(hidden) = walked.get(&hash) {
+            stack.extend(direct(hidden).into_iter().rev());
+        }
+    }
+    parents
+}
@@ -1,15 +1,20 @@
 use std::fs;
 use anyhow::Result;
-use crate::core::{Repository, Object, Commit};
-use crate::core::merge::merge_commits;
+use crate::core::{hooks, Repository, Object, Commit, Tree, Config};
+use crate::core::attributes::Attributes;
+use crate::core::merge::{conflict_path, merge_commits};
+use crate::core::revwalk::merge_base;
+use crate::core::worktree::checkout_tree;
+use crate::utils::diff::DiffOptions;
 
-pub fn execute(branch_name: String) -> Result<()> {
+pub fn execute(branch_name: String, options: DiffOptions, no_verify: bool) -> Result<()> {
     let repo = Repository::find_repository()?;
-    
+    let config = Config::load(Some(&repo))?;
+
     // Get current branch and commit
     let current_branch = repo.get_current_branch()?
         .ok_or_else(|| anyhow::anyhow!("Not on a branch - cannot merge"))?;
-    
+
     let current_commit = repo.get_head()?
         .ok_or_else(|| anyhow::anyhow!("No commits yet"))?;
 
@@ -18,21 +23,53 @@ pub fn execute(branch_name: String) -> Result<()> {
     if !branch_file.exists() {
         return Err(anyhow::anyhow!("Branch '{}' does not exist", branch_name));
     }
-    
+
     let target_commit = fs::read_to_string(&branch_file)?.trim().to_string();
 
-    if current_commit == target_commit {
+    // Find common ancestor
+    let base_commit = merge_base(&repo, &current_commit, &target_commit)?
+        .ok_or_else(|| anyhow::anyhow!("Refusing to merge unrelated histories"))?;
+
+    if base_commit == target_commit {
         println!("Already up to date.");
         return Ok(());
     }
 
-    // Find common ancestor (simplified - just use current commit as base for now)
-    let base_commit = current_commit.clone();
+    let our_tree = repo.load_commit(&current_commit)?.tree;
+    if base_commit == current_commit {
+        // Nothing to merge on our side: move the branch forward
+        checkout_tree(&repo, Some(&our_tree), &repo.load_commit(&target_commit)?.tree)?;
+        repo.update_ref(&format!("refs/heads/{}", current_branch), &target_commit)?;
+        println!("Fast-forward to {}", &target_commit[..8]);
+        return Ok(());
+    }
 
     // Perform merge
-    let merge_result = merge_commits(&repo, &base_commit, &current_commit, &target_commit)?;
+    let merge_result = merge_commits(&repo, &base_commit, &current_commit, &target_commit, &options)?;
+    let tree_hash = merge_result.merged_tree.unwrap();
+    if merge_result.success && !no_verify {
+        // Checked before the working tree changes, so a refusal leaves it as it was
+        hooks::run(&repo, "pre-merge", &[])?;
+    }
+    checkout_tree(&repo, Some(&our_tree), &tree_hash)?;
 
     if !merge_result.success {
+        // Stage what merged cleanly and leave the conflicts to be resolved
+        let mut index = repo.load_index()?;
+        let attributes = Attributes::load(&repo)?;
+        for change in load_tree(&repo, &tree_hash)?.changes_from(&load_tree(&repo, &our_tree)?) {
+            let conflicted = merge_result.conflicts.iter().any(|conflict| conflict_path(conflict) == change.path);
+            if conflicted {
+                continue;
+            }
+            match change.new_hash {
+                Some(_) => index.add_file(&repo, repo.root.join(&change.path), &attributes)?,
+                None => index.stage_removal(&change.path),
+            }
+        }
+        repo.save_index(&index)?;
+        fs::write(repo.nvcs_dir.join("MERGE_HEAD"), format!("{}\n", target_commit))?;
+
         println!("Merge conflicts in:");
         for conflict in &merge_result.conflicts {
             println!("  {}", conflict);
@@ -42,11 +79,11 @@ pub fn execute(branch_name: String) -> Result<()> {
     }
 
     // Create merge commit
-    let tree_hash = merge_result.merged_tree.unwrap();
     let merge_commit = Commit::new(
         tree_hash,
         vec![current_commit, target_commit],
-        "System".to_string(),
+        config.author()?,
+        config.committer()?,
         format!("Merge branch '{}'", branch_name),
     );
 
@@ -60,4 +97,11 @@ pub fn execute(branch_name: String) -> Result<()> {
     println!("Merge commit: {}", merge_commit.short_hash());
 
     Ok(())
-}
\ No newline at end of file
+}
+
+fn load_tree(repo: &Repository, hash: &str) -> Result<Tree> {
+    repo.load_object(hash)?
+        .as_tree()
+        .cloned()
+        .ok_or_else(|| anyhow::anyhow!("Object {} is not a tree", hash))
+}
@@ -9,4 +9,15 @@ pub mod checkout;
 pub mod merge;
 pub mod show;
 pub mod reset;
-pub mod rm;
\ No newline at end of file
+pub mod rm;
+pub mod apply;
+pub mod blame;
+pub mod bisect;
+pub mod stash;
+pub mod sequencer;
+pub mod cherry_pick;
+pub mod revert;
+pub mod rebase;
+pub mod config;
+pub mod check_ignore;
+pub mod check_attr;
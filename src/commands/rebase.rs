@@ -0,0 +1,474 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::Result;
+use crate::core::{Repository, Object, Commit, Config, Index, Signature};
+use crate::core::revwalk::{merge_base, RevWalk, WalkOrder};
+use crate::core::worktree::{checkout_tree, reset_worktree, write_index_tree, write_tree_with_index};
+use crate::commands::sequencer::{commit_tree, merge_change, Action};
+use crate::utils::editor::{edit_file, strip_comments};
+
+const TODO_HELP: &str = "
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> = use commit, but edit the commit message
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\", but discard this commit's message
+# d, drop <commit> = remove commit
+#
+# These lines can be re-ordered; they are executed from top to bottom.
+# If you remove everything, the rebase will be aborted.
+";
+
+/// What to do with one commit of the todo list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Pick,
+    Reword,
+    Edit,
+    Squash,
+    Fixup,
+    Drop,
+}
+
+impl Step {
+    fn parse(word: &str) -> Option<Self> {
+        match word {
+            "p" | "pick" => Some(Step::Pick),
+            "r" | "reword" => Some(Step::Reword),
+            "e" | "edit" => Some(Step::Edit),
+            "s" | "squash" => Some(Step::Squash),
+            "f" | "fixup" => Some(Step::Fixup),
+            "d" | "drop" => Some(Step::Drop),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Step::Pick => "pick",
+            Step::Reword => "reword",
+            Step::Edit => "edit",
+            Step::Squash => "squash",
+            Step::Fixup => "fixup",
+            Step::Drop => "drop",
+        }
+    }
+}
+
+/// How the rebase carries on after a step.
+enum Flow {
+    Next,
+    /// Stopped on purpose (`edit`) so the user can amend
+    Stopped,
+    /// Stopped on a conflict
+    Conflicted,
+}
+
+/// Replays the commits of the current branch that are not in `upstream` on
+/// top of it. With `interactive`, the todo list is edited first; with
+/// `autosquash`, "fixup!" and "squash!" commits are moved after their targets.
+pub fn start(upstream: String, interactive: bool, autosquash: bool) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    if state_dir(&repo).exists() {
+        return Err(anyhow::anyhow!(
+            "A rebase is already in progress (use --continue, --skip or --abort)"
+        ));
+    }
+    if !repo.load_index()?.entries.is_empty() {
+        return Err(anyhow::anyhow!("You have staged changes; commit them before you rebase"));
+    }
+
+    let head = repo.get_head()?.ok_or_else(|| anyhow::anyhow!("No commits yet"))?;
+    let onto = repo.resolve_revision(&upstream)?;
+    let base = merge_base(&repo, &head, &onto)?;
+    if base.as_deref() == Some(onto.as_str()) && !interactive && !autosquash {
+        println!("Current branch is up to date.");
+        return Ok(());
+    }
+
+    // Oldest first; merge commits are flattened away
+    let mut walk = RevWalk::new(&repo);
+    walk.push(head.clone());
+    walk.hide(onto.clone());
+    walk.order = WalkOrder::Topo;
+    let commits: Vec<(String, Commit)> = walk.commits()?
+        .into_iter()
+        .rev()
+        .filter(|(_, commit)| !commit.is_merge())
+        .collect();
+
+    fs::create_dir_all(state_dir(&repo))?;
+    let mut steps: Vec<(Step, &str, &str)> = commits
+        .iter()
+        .map(|(hash, commit)| (Step::Pick, hash.as_str(), subject(commit)))
+        .collect();
+    if autosquash {
+        steps = arrange_autosquash(steps);
+    }
+    let mut todo: String = steps
+        .iter()
+        .map(|(step, hash, subject)| format!("{} {} {}\n", step.name(), hash, subject))
+        .collect();
+    write_state(&repo, "head-name", &repo.get_current_branch()?.unwrap_or_default())?;
+    write_state(&repo, "orig-head", &head)?;
+    write_state(&repo, "onto", &onto)?;
+    if interactive {
+        // Nothing has changed yet, so a failed or emptied edit leaves no rebase behind
+        todo = match edit_todo(&repo, &todo, &onto) {
+            Ok(Some(todo)) => todo,
+            Ok(None) => {
+                fs::remove_dir_all(state_dir(&repo))?;
+                println!("Nothing to do");
+                return Ok(());
+            }
+            Err(error) => {
+                fs::remove_dir_all(state_dir(&repo))?;
+                return Err(error);
+            }
+        };
+    }
+    write_state(&repo, "todo", &todo)?;
+
+    // Replay on a detached HEAD; the branch only moves once everything applied
+    let head_tree = repo.load_commit(&head)?.tree;
+    checkout_tree(&repo, Some(&head_tree), &repo.load_commit(&onto)?.tree)?;
+    fs::write(&repo.head_file, format!("{}\n", onto))?;
+
+    proceed(&repo)
+}
+
+/// Finishes the step the rebase stopped at and replays the rest.
+pub fn resume() -> Result<()> {
+    let repo = Repository::find_repository()?;
+    ensure_rebasing(&repo)?;
+
+    if let Some((step, hash)) = stopped_step(&repo)? {
+        let mut index = repo.load_index()?;
+        match read_state(&repo, "merged")? {
+            Some(merged) => {
+                let unresolved: Vec<String> = read_state(&repo, "conflicts")?
+                    .unwrap_or_default()
+                    .lines()
+                    .filter(|path| !index.is_staged(path))
+                    .map(str::to_string)
+                    .collect();
+                if !unresolved.is_empty() {
+                    return Err(anyhow::anyhow!(
+                        "You need to resolve and add these files first:\n  {}",
+                        unresolved.join("\n  ")
+                    ));
+                }
+                let tree = write_tree_with_index(&repo, Some(&merged), &index)?;
+                clear_stop(&repo)?;
+                if let Flow::Stopped = finish_step(&repo, step, &hash, tree)? {
+                    return Ok(());
+                }
+            }
+            None => {
+                // Stopped by `edit`: staged changes go into the edited commit
+                if !index.entries.is_empty() {
+                    let tree = write_index_tree(&repo, &index)?;
+                    let head = repo.load_commit(&current_head(&repo)?)?;
+                    replace_head(&repo, tree, head.author, head.message)?;
+                    index.clear();
+                    repo.save_index(&index)?;
+                }
+                clear_stop(&repo)?;
+            }
+        }
+    }
+
+    proceed(&repo)
+}
+
+/// Drops the commit the rebase stopped at and replays the rest.
+pub fn skip() -> Result<()> {
+    let repo = Repository::find_repository()?;
+    ensure_rebasing(&repo)?;
+
+    if let Some(merged) = read_state(&repo, "merged")? {
+        let head_tree = repo.load_commit(&current_head(&repo)?)?.tree;
+        reset_worktree(&repo, Some(&merged), &head_tree)?;
+        repo.save_index(&Index::new())?;
+    }
+    clear_stop(&repo)?;
+    proceed(&repo)
+}
+
+/// Puts the branch, index and working tree back as they were before the
+/// rebase started.
+pub fn abort() -> Result<()> {
+    let repo = Repository::find_repository()?;
+    ensure_rebasing(&repo)?;
+
+    let original = read_state(&repo, "orig-head")?
+        .filter(|hash| !hash.is_empty())
+        .ok_or_else(|| anyhow::anyhow!(
+            "The rebase state has no original HEAD; remove {} to give up",
+            state_dir(&repo).display()
+        ))?;
+    let current_tree = match read_state(&repo, "merged")? {
+        Some(merged) => merged,
+        None => write_index_tree(&repo, &repo.load_index()?)?,
+    };
+    reset_worktree(&repo, Some(&current_tree), &repo.load_commit(&original)?.tree)?;
+    repo.save_index(&Index::new())?;
+
+    match read_state(&repo, "head-name")?.filter(|name| !name.is_empty()) {
+        Some(branch) => repo.checkout_branch(&branch)?,
+        None => fs::write(&repo.head_file, format!("{}\n", original))?,
+    }
+    fs::remove_dir_all(state_dir(&repo))?;
+    println!("Rebase aborted; HEAD is back at {}", &original[..8]);
+    Ok(())
+}
+
+fn proceed(repo: &Repository) -> Result<()> {
+    loop {
+        let todo = read_state(repo, "todo")?.unwrap_or_default();
+        let mut lines = todo.lines();
+        let Some(line) = lines.next() else {
+            return finish(repo);
+        };
+        let (step, hash) = parse_line(line)?;
+        let rest: String = lines.map(|line| format!("{}\n", line)).collect();
+
+        let flow = run_step(repo, step, &hash)?;
+        write_state(repo, "todo", &rest)?;
+
+        match flow {
+            Flow::Next => {}
+            Flow::Stopped => return Ok(()),
+            Flow::Conflicted => {
+                let commit = repo.load_commit(&hash)?;
+                return Err(anyhow::anyhow!(
+                    "Could not apply {}... {}\nResolve the conflicts, \"nvcs add\" the files and run \"nvcs rebase --continue\",\nrun \"nvcs rebase --skip\" to drop this commit, or \"nvcs rebase --abort\" to give up",
+                    &hash[..8],
+                    subject(&commit)
+                ));
+            }
+        }
+    }
+}
+
+fn run_step(repo: &Repository, step: Step, hash: &str) -> Result<Flow> {
+    if step == Step::Drop {
+        return Ok(Flow::Next);
+    }
+
+    let change = merge_change(repo, Action::Pick, hash, None)?;
+    if !change.conflicts.is_empty() {
+        write_state(repo, "stopped", &format!("{} {}", step.name(), hash))?;
+        write_state(repo, "merged", &change.merged)?;
+        write_state(repo, "conflicts", &change.conflicts.join("\n"))?;
+        return Ok(Flow::Conflicted);
+    }
+    finish_step(repo, step, hash, change.merged)
+}
+
+/// Records the replayed commit once its tree is known.
+fn finish_step(repo: &Repository, step: Step, hash: &str, tree: String) -> Result<Flow> {
+    let commit = repo.load_commit(hash)?;
+    let head = repo.load_commit(&current_head(repo)?)?;
+
+    match step {
+        Step::Squash => {
+            // The "squash!" subject only named the target
+            let added = match commit.message.split_once('\n') {
+                Some((first, rest)) if first.starts_with("squash! ") => rest.trim(),
+                None if commit.message.starts_with("squash! ") => "",
+                _ => commit.message.trim_end(),
+            };
+            let combined = format!("{}\n\n{}", head.message.trim_end(), added);
+            let message = edit_message(repo, &combined)?;
+            replace_head(repo, tree, head.author, message)?;
+        }
+        Step::Fixup => replace_head(repo, tree, head.author, head.message)?,
+        _ if tree == head.tree => {
+            println!("Skipping {}... {}: its changes are already applied", &hash[..8], subject(&commit));
+        }
+        _ => {
+            let message = match step {
+                Step::Reword => edit_message(repo, &commit.message)?,
+                _ => commit.message.clone(),
+            };
+            let config = Config::load(Some(repo))?;
+            commit_tree(repo, &config, &format!("rebase ({})", step.name()), tree, commit.author.clone(), message)?;
+        }
+    }
+
+    if step == Step::Edit {
+        write_state(repo, "stopped", &format!("{} {}", step.name(), hash))?;
+        println!("Stopped at {}... {}", &hash[..8], subject(&commit));
+        println!("You can amend the commit now, then run \"nvcs rebase --continue\"");
+        return Ok(Flow::Stopped);
+    }
+    Ok(Flow::Next)
+}
+
+/// Moves the branch to the replayed commits and leaves the rebase.
+fn finish(repo: &Repository) -> Result<()> {
+    let head = current_head(repo)?;
+    match read_state(repo, "head-name")?.filter(|name| !name.is_empty()) {
+        Some(branch) => {
+            repo.update_ref(&format!("refs/heads/{}", branch), &head)?;
+            repo.checkout_branch(&branch)?;
+            println!("Successfully rebased and updated refs/heads/{}.", branch);
+        }
+        None => println!("Successfully rebased; HEAD is now at {}.", &head[..8]),
+    }
+    fs::remove_dir_all(state_dir(repo))?;
+    Ok(())
+}
+
+/// Replaces HEAD with a commit of `tree` on HEAD's parents.
+fn replace_head(repo: &Repository, tree: String, author: Signature, message: String) -> Result<()> {
+    let head = repo.load_commit(&current_head(repo)?)?;
+    let committer = Config::load(Some(repo))?.committer()?;
+    let commit = Commit::new(tree, head.parents, author, committer, message);
+    let hash = repo.store_object(&Object::Commit(commit.clone()))?;
+    repo.update_head(&hash)?;
+    println!("[detached HEAD {}] {}", &hash[..8], subject(&commit));
+    Ok(())
+}
+
+/// Moves each "fixup! <subject>" or "squash! <subject>" commit right after
+/// the commit it names (by subject or hash prefix) and its earlier fixups.
+fn arrange_autosquash<'a>(steps: Vec<(Step, &'a str, &'a str)>) -> Vec<(Step, &'a str, &'a str)> {
+    let mut arranged: Vec<(Step, &str, &str)> = Vec::with_capacity(steps.len());
+    for (step, hash, subject) in steps {
+        let marked = subject.strip_prefix("fixup! ").map(|target| (Step::Fixup, target))
+            .or_else(|| subject.strip_prefix("squash! ").map(|target| (Step::Squash, target)));
+        let position = marked.and_then(|(_, target)| {
+            arranged.iter().position(|(_, hash, subject)| *subject == target || hash.starts_with(target))
+        });
+        match (marked, position) {
+            (Some((marked_step, _)), Some(position)) => {
+                let mut at = position + 1;
+                while at < arranged.len() && matches!(arranged[at].0, Step::Fixup | Step::Squash) {
+                    at += 1;
+                }
+                arranged.insert(at, (marked_step, hash, subject));
+            }
+            _ => arranged.push((step, hash, subject)),
+        }
+    }
+    arranged
+}
+
+/// Writes the todo list with short hashes for the user to edit and reads it
+/// back with full hashes. Returns `None` if every line was removed.
+fn edit_todo(repo: &Repository, todo: &str, onto: &str) -> Result<Option<String>> {
+    let file = state_dir(repo).join("git-rebase-todo");
+    let mut content: String = todo
+        .lines()
+        .map(|line| {
+            let mut fields = line.splitn(3, ' ');
+            let (step, hash, subject) = (fields.next().unwrap_or(""), fields.next().unwrap_or(""), fields.next().unwrap_or(""));
+            format!("{} {} {}\n", step, &hash[..8], subject)
+        })
+        .collect();
+    let count = todo.lines().count();
+    content.push_str(&format!(
+        "\n# Rebase onto {} ({} command{})",
+        &onto[..8],
+        count,
+        if count == 1 { "" } else { "s" }
+    ));
+    content.push_str(TODO_HELP);
+    fs::write(&file, content)?;
+    edit_file(&file)?;
+
+    let mut edited = String::new();
+    let mut have_commit = false;
+    for line in fs::read_to_string(&file)?.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.splitn(3, ' ');
+        let word = fields.next().unwrap_or("");
+        let step = Step::parse(word)
+            .ok_or_else(|| anyhow::anyhow!("Invalid todo command '{}' in line: {}", word, line))?;
+        let revision = fields.next()
+            .ok_or_else(|| anyhow::anyhow!("Missing commit in todo line: {}", line))?;
+        let hash = repo.resolve_revision(revision)?;
+        if matches!(step, Step::Squash | Step::Fixup) && !have_commit {
+            return Err(anyhow::anyhow!("Cannot '{}' without a previous commit", step.name()));
+        }
+        have_commit |= step != Step::Drop;
+        edited.push_str(&format!("{} {} {}\n", step.name(), hash, fields.next().unwrap_or("")));
+    }
+    fs::remove_file(&file)?;
+    Ok(if edited.is_empty() { None } else { Some(edited) })
+}
+
+/// Lets the user edit a commit message; an emptied message keeps `message`.
+fn edit_message(repo: &Repository, message: &str) -> Result<String> {
+    let file = state_dir(repo).join("message");
+    fs::write(&file, format!(
+        "{}\n\n# Please enter the commit message for your changes. Lines starting\n# with '#' will be ignored.\n",
+        message.trim_end()
+    ))?;
+    edit_file(&file)?;
+    let edited = strip_comments(&fs::read_to_string(&file)?);
+    fs::remove_file(&file)?;
+    Ok(if edited.is_empty() { message.to_string() } else { edited })
+}
+
+fn parse_line(line: &str) -> Result<(Step, String)> {
+    let mut fields = line.split(' ');
+    let step = fields.next().and_then(Step::parse);
+    match (step, fields.next()) {
+        (Some(step), Some(hash)) => Ok((step, hash.to_string())),
+        _ => Err(anyhow::anyhow!("Corrupt rebase todo line: {}", line)),
+    }
+}
+
+fn stopped_step(repo: &Repository) -> Result<Option<(Step, String)>> {
+    read_state(repo, "stopped")?.map(|line| parse_line(&line)).transpose()
+}
+
+fn clear_stop(repo: &Repository) -> Result<()> {
+    for name in ["stopped", "merged", "conflicts"] {
+        let file = state_dir(repo).join(name);
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+    }
+    Ok(())
+}
+
+fn current_head(repo: &Repository) -> Result<String> {
+    repo.get_head()?.ok_or_else(|| anyhow::anyhow!("HEAD is not set"))
+}
+
+fn subject(commit: &Commit) -> &str {
+    commit.message.lines().next().unwrap_or("")
+}
+
+fn ensure_rebasing(repo: &Repository) -> Result<()> {
+    if state_dir(repo).exists() {
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("No rebase in progress"))
+    }
+}
+
+fn state_dir(repo: &Repository) -> PathBuf {
+    repo.nvcs_dir.join("rebase-merge")
+}
+
+fn read_state(repo: &Repository, name: &str) -> Result<Option<String>> {
+    let file = state_dir(repo).join(name);
+    if file.exists() {
+        Ok(Some(fs::read_to_string(file)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn write_state(repo: &Repository, name: &str, content: &str) -> Result<()> {
+    fs::write(state_dir(repo).join(name), content)?;
+    Ok(())
+}
@@ -1,17 +1,20 @@
 use std::fs;
 use anyhow::Result;
-use crate::core::{Repository, Index};
+use crate::core::{hooks, Repository, Index};
+use crate::core::reflog::ZERO_HASH;
+use crate::core::worktree::{reset_worktree, write_index_tree};
 
 pub fn execute(mode: String, target: Option<String>) -> Result<()> {
     let repo = Repository::find_repository()?;
     
     let target_commit = match target {
-        Some(t) => t,
+        Some(revision) => repo.resolve_revision(&revision)?,
         None => repo.get_head()?.ok_or_else(|| anyhow::anyhow!("No commits yet"))?,
     };
 
     // Verify target commit exists
-    repo.load_object(&target_commit)?;
+    let target_tree = repo.load_commit(&target_commit)?.tree;
+    let previous = repo.get_head()?.unwrap_or_else(|| ZERO_HASH.to_string());
 
     match mode.as_str() {
         "soft" => {
@@ -39,6 +42,8 @@ pub fn execute(mode: String, target: Option<String>) -> Result<()> {
         }
         "hard" => {
             // Move HEAD, reset index, and reset working directory
+            let current_tree = write_index_tree(&repo, &repo.load_index()?)?;
+            reset_worktree(&repo, Some(&current_tree), &target_tree)?;
             if let Some(current_branch) = repo.get_current_branch()? {
                 repo.update_ref(&format!("refs/heads/{}", current_branch), &target_commit)?;
             } else {
@@ -49,16 +54,13 @@ pub fn execute(mode: String, target: Option<String>) -> Result<()> {
             let empty_index = Index::new();
             repo.save_index(&empty_index)?;
             
-            // TODO: Reset working directory files to match target commit
-            // This would involve reading the tree from target commit and updating files
-            
             println!("Hard reset to {}", &target_commit[..8]);
-            println!("Warning: Working directory changes not implemented yet");
         }
         _ => {
             return Err(anyhow::anyhow!("Invalid reset mode: {}. Use soft, mixed, or hard", mode));
         }
     }
 
+    hooks::run_post(&repo, "post-reset", &[&previous, &target_commit, &mode]);
     Ok(())
 }
\ No newline at end of file
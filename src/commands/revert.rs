@@ -0,0 +1,17 @@
+use anyhow::Result;
+use crate::commands::sequencer::{self, Action, SequenceOptions};
+
+pub fn execute(
+    revisions: Vec<String>,
+    options: SequenceOptions,
+    resume: bool,
+    abort: bool,
+) -> Result<()> {
+    if resume {
+        sequencer::resume(Action::Revert)
+    } else if abort {
+        sequencer::abort(Action::Revert)
+    } else {
+        sequencer::start(Action::Revert, &revisions, options)
+    }
+}
@@ -2,10 +2,12 @@ use std::fs;
 use std::path::Path;
 use anyhow::Result;
 use crate::core::Repository;
+use crate::core::worktree::head_files;
 
 pub fn execute(files: Vec<String>, cached: bool) -> Result<()> {
     let repo = Repository::find_repository()?;
     let mut index = repo.load_index()?;
+    let tracked = head_files(&repo)?;
 
     if files.is_empty() {
         return Err(anyhow::anyhow!("No files specified"));
@@ -19,13 +21,18 @@ pub fn execute(files: Vec<String>, cached: bool) -> Result<()> {
             file_path
         };
 
-        if !index.is_staged(relative_path) {
-            eprintln!("Warning: File not staged: {}", file_str);
+        let committed = tracked.contains_key(relative_path.to_string_lossy().as_ref());
+        if !committed && !index.is_staged(relative_path) {
+            eprintln!("Warning: File not tracked: {}", file_str);
             continue;
         }
 
-        // Remove from index
-        index.remove_file(relative_path);
+        // Remove from index, staging the deletion of committed files
+        if committed {
+            index.stage_removal(relative_path);
+        } else {
+            index.remove_file(relative_path);
+        }
         println!("Removed from index: {}", file_str);
 
         // Remove from working directory if not --cached
@@ -0,0 +1,343 @@
+use std::fs;
+use std::path::PathBuf;
+use anyhow::Result;
+use crate::core::{Repository, Object, Commit, Tree, Index, Config, Signature};
+use crate::core::merge::{conflict_path, merge_tree_hashes};
+use crate::core::reflog;
+use crate::core::worktree::{checkout_tree, reset_worktree, stage_tree_changes, write_index_tree, write_tree_with_index};
+use crate::utils::diff::DiffOptions;
+
+/// What to do with each commit in the sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Apply the commit's change (cherry-pick)
+    Pick,
+    /// Apply the inverse of the commit's change (revert)
+    Revert,
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Pick => "cherry-pick",
+            Action::Revert => "revert",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SequenceOptions {
+    /// Append "(cherry picked from commit ...)" to picked messages
+    pub record_origin: bool,
+    /// Parent number (1-based) to diff merge commits against
+    pub mainline: Option<usize>,
+    /// Apply the changes to the working tree and index without committing
+    pub no_commit: bool,
+}
+
+enum Outcome {
+    Applied,
+    Conflicted,
+}
+
+/// Applies `revisions` one after another onto HEAD, stopping at the first
+/// conflict so it can be resolved and resumed with `resume`.
+pub fn start(action: Action, revisions: &[String], options: SequenceOptions) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    if state_dir(&repo).exists() {
+        return Err(anyhow::anyhow!(
+            "A cherry-pick or revert is already in progress (use --continue or --abort)"
+        ));
+    }
+    if revisions.is_empty() {
+        return Err(anyhow::anyhow!("No commits given to {}", action.name()));
+    }
+    let head = repo.get_head()?.ok_or_else(|| anyhow::anyhow!("No commits yet"))?;
+    if !options.no_commit && !repo.load_index()?.entries.is_empty() {
+        return Err(anyhow::anyhow!("You have staged changes; commit them before you {}", action.name()));
+    }
+    let commits = revisions
+        .iter()
+        .map(|revision| repo.resolve_revision(revision))
+        .collect::<Result<Vec<_>>>()?;
+    // Catch a missing or stray -m before anything has been changed
+    for hash in &commits {
+        base_parent(&repo.load_commit(hash)?, hash, options.mainline)?;
+    }
+
+    fs::create_dir_all(state_dir(&repo))?;
+    repo.load_index()?.save(state_dir(&repo).join("index"))?;
+    write_state(&repo, "head", &head)?;
+    write_state(&repo, "action", action.name())?;
+    write_state(&repo, "options", &format!(
+        "{} {} {}",
+        options.record_origin,
+        options.mainline.unwrap_or(0),
+        options.no_commit
+    ))?;
+    write_state(&repo, "todo", &commits.join("\n"))?;
+
+    proceed(&repo, action, options)
+}
+
+/// Commits the resolved conflict and carries on with the remaining commits.
+pub fn resume(action: Action) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let options = load_state(&repo, action)?;
+
+    if read_state(&repo, "current")?.is_some() {
+        let index = repo.load_index()?;
+        let unresolved: Vec<String> = read_state(&repo, "conflicts")?
+            .unwrap_or_default()
+            .lines()
+            .filter(|path| !index.is_staged(path))
+            .map(str::to_string)
+            .collect();
+        if !unresolved.is_empty() {
+            return Err(anyhow::anyhow!(
+                "You need to resolve and add these files first:\n  {}",
+                unresolved.join("\n  ")
+            ));
+        }
+
+        let merged = read_state(&repo, "merged")?.unwrap_or_default();
+        let resolved = write_tree_with_index(&repo, Some(&merged), &index)?;
+        if options.no_commit {
+            let ours = read_state(&repo, "ours")?.unwrap_or_default();
+            let mut index = index;
+            stage_tree_changes(&repo, &mut index, &ours, &resolved)?;
+            repo.save_index(&index)?;
+        } else {
+            let message = read_state(&repo, "message")?.unwrap_or_default();
+            let author: Signature = serde_json::from_str(&read_state(&repo, "author")?.unwrap_or_default())?;
+            commit_tree(&repo, &Config::load(Some(&repo))?, action.name(), resolved, author, message)?;
+        }
+        for name in ["current", "merged", "ours", "conflicts", "message", "author"] {
+            remove_state(&repo, name)?;
+        }
+    }
+
+    proceed(&repo, action, options)
+}
+
+/// Returns the branch, index and working tree to where they were before
+/// the sequence started.
+pub fn abort(action: Action) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    load_state(&repo, action)?;
+
+    let original = read_state(&repo, "head")?.unwrap_or_default();
+    let saved_index = Index::load(state_dir(&repo).join("index"))?;
+    let original_tree = write_tree_with_index(&repo, Some(&repo.load_commit(&original)?.tree), &saved_index)?;
+    let current_tree = match read_state(&repo, "merged")? {
+        Some(merged) => merged,
+        None => write_index_tree(&repo, &repo.load_index()?)?,
+    };
+
+    reset_worktree(&repo, Some(&current_tree), &original_tree)?;
+    repo.update_head(&original)?;
+    repo.save_index(&saved_index)?;
+    fs::remove_dir_all(state_dir(&repo))?;
+    println!("{} aborted; HEAD is back at {}", capitalize(action.name()), &original[..8]);
+    Ok(())
+}
+
+fn proceed(repo: &Repository, action: Action, options: SequenceOptions) -> Result<()> {
+    loop {
+        let todo = read_state(repo, "todo")?.unwrap_or_default();
+        let mut commits = todo.lines();
+        let Some(next) = commits.next() else {
+            fs::remove_dir_all(state_dir(repo))?;
+            return Ok(());
+        };
+        let next = next.to_string();
+        let outcome = apply_commit(repo, action, &next, options)?;
+        write_state(repo, "todo", &commits.collect::<Vec<_>>().join("\n"))?;
+
+        if let Outcome::Conflicted = outcome {
+            let commit = repo.load_commit(&next)?;
+            return Err(anyhow::anyhow!(
+                "Could not {} {}... {}\nResolve the conflicts, \"nvcs add\" the files and run \"nvcs {} --continue\",\nor run \"nvcs {} --abort\" to give up",
+                action.name(),
+                &next[..8],
+                commit.message.lines().next().unwrap_or(""),
+                action.name(),
+                action.name()
+            ));
+        }
+    }
+}
+
+/// Merges one commit's change (or its inverse) into HEAD and the working tree.
+fn apply_commit(repo: &Repository, action: Action, hash: &str, options: SequenceOptions) -> Result<Outcome> {
+    let commit = repo.load_commit(hash)?;
+    let config = Config::load(Some(repo))?;
+    let subject = commit.message.lines().next().unwrap_or("");
+    let (message, author) = match action {
+        Action::Pick if options.record_origin => (
+            format!("{}\n\n(cherry picked from commit {})", commit.message.trim_end(), hash),
+            commit.author.clone(),
+        ),
+        Action::Pick => (commit.message.clone(), commit.author.clone()),
+        Action::Revert => (
+            format!("Revert \"{}\"\n\nThis reverts commit {}.", subject, hash),
+            config.author()?,
+        ),
+    };
+
+    let change = merge_change(repo, action, hash, options.mainline)?;
+    if !change.conflicts.is_empty() {
+        write_state(repo, "current", hash)?;
+        write_state(repo, "merged", &change.merged)?;
+        write_state(repo, "ours", &change.ours)?;
+        write_state(repo, "conflicts", &change.conflicts.join("\n"))?;
+        write_state(repo, "message", &message)?;
+        write_state(repo, "author", &serde_json::to_string(&author)?)?;
+        return Ok(Outcome::Conflicted);
+    }
+
+    if options.no_commit {
+        let mut index = repo.load_index()?;
+        stage_tree_changes(repo, &mut index, &change.ours, &change.merged)?;
+        repo.save_index(&index)?;
+    } else if change.merged == change.ours {
+        println!("Skipping {}... {}: its changes are already applied", &hash[..8], subject);
+    } else {
+        commit_tree(repo, &config, action.name(), change.merged, author, message)?;
+    }
+    Ok(Outcome::Applied)
+}
+
+/// A commit's change after it has been merged into the working tree.
+pub struct MergedChange {
+    /// HEAD's tree with the staged changes, before the merge
+    pub ours: String,
+    /// The merged tree; files that conflicted hold conflict markers
+    pub merged: String,
+    /// Paths that did not merge cleanly
+    pub conflicts: Vec<String>,
+}
+
+/// Three-way merges the change `hash` made relative to its (`mainline`)
+/// parent, or for a revert the inverse of it, into HEAD and the working tree.
+pub fn merge_change(repo: &Repository, action: Action, hash: &str, mainline: Option<usize>) -> Result<MergedChange> {
+    let commit = repo.load_commit(hash)?;
+    let parent_tree = match base_parent(&commit, hash, mainline)? {
+        Some(parent) => repo.load_commit(&parent)?.tree,
+        None => repo.store_object(&Object::Tree(Tree::new()))?,
+    };
+
+    let subject = commit.message.lines().next().unwrap_or("");
+    let (base, theirs, label) = match action {
+        Action::Pick => (parent_tree, commit.tree, format!("{} ({})", &hash[..8], subject)),
+        Action::Revert => (commit.tree, parent_tree, format!("parent of {} ({})", &hash[..8], subject)),
+    };
+
+    let ours = write_index_tree(repo, &repo.load_index()?)?;
+    let options = DiffOptions::from_config(&Config::load(Some(repo))?)?;
+    let result = merge_tree_hashes(repo, &base, &ours, &theirs, &options, ("HEAD", &label))?;
+    let merged = result.merged_tree.expect("merges always produce a tree");
+    checkout_tree(repo, Some(&ours), &merged)?;
+
+    for conflict in &result.conflicts {
+        println!("CONFLICT: Merge conflict in {}", conflict);
+    }
+    let conflicts = result.conflicts.iter().map(|conflict| conflict_path(conflict).to_string()).collect();
+    Ok(MergedChange { ours, merged, conflicts })
+}
+
+/// Picks the parent whose tree the commit's change is measured against.
+fn base_parent(commit: &Commit, hash: &str, mainline: Option<usize>) -> Result<Option<String>> {
+    match (commit.parents.len(), mainline) {
+        (0, None) => Ok(None),
+        (1, None) => Ok(Some(commit.parents[0].clone())),
+        (_, None) => Err(anyhow::anyhow!("Commit {} is a merge but no -m option was given", &hash[..8])),
+        (count, Some(mainline)) if count > 1 && (1..=count).contains(&mainline) => {
+            Ok(Some(commit.parents[mainline - 1].clone()))
+        }
+        (count, Some(mainline)) if count > 1 => {
+            Err(anyhow::anyhow!("Commit {} does not have parent {}", &hash[..8], mainline))
+        }
+        (_, Some(_)) => Err(anyhow::anyhow!("Mainline was specified but commit {} is not a merge", &hash[..8])),
+    }
+}
+
+/// Commits `tree` on top of HEAD with the committer from `config` and clears
+/// the index. `reflog_action` prefixes the subject in the reflog entry.
+pub fn commit_tree(
+    repo: &Repository,
+    config: &Config,
+    reflog_action: &str,
+    tree: String,
+    author: Signature,
+    message: String,
+) -> Result<()> {
+    let head = repo.get_head()?.ok_or_else(|| anyhow::anyhow!("No commits yet"))?;
+    let committer = config.committer()?;
+    let commit = Commit::new(tree, vec![head.clone()], author, committer, message);
+    let hash = repo.store_object(&Object::Commit(commit.clone()))?;
+    repo.update_head(&hash)?;
+    repo.save_index(&Index::new())?;
+
+    let subject = commit.message.lines().next().unwrap_or("");
+    let reflog_message = format!("{}: {}", reflog_action, subject);
+    reflog::append(repo, "HEAD", Some(&head), &hash, &reflog_message)?;
+    let branch = repo.get_current_branch()?;
+    if let Some(branch) = &branch {
+        reflog::append(repo, &format!("refs/heads/{}", branch), Some(&head), &hash, &reflog_message)?;
+    }
+
+    let branch = branch.unwrap_or_else(|| "detached HEAD".to_string());
+    println!("[{} {}] {}", branch, &hash[..8], subject);
+    Ok(())
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Checks that a sequence of this kind is in progress and reads its options.
+fn load_state(repo: &Repository, action: Action) -> Result<SequenceOptions> {
+    match read_state(repo, "action")? {
+        Some(name) if name == action.name() => {}
+        Some(name) => return Err(anyhow::anyhow!("A {} is in progress, not a {}", name, action.name())),
+        None => return Err(anyhow::anyhow!("No {} in progress", action.name())),
+    }
+    let options = read_state(repo, "options")?.unwrap_or_default();
+    let fields: Vec<&str> = options.split(' ').collect();
+    Ok(SequenceOptions {
+        record_origin: fields.first() == Some(&"true"),
+        mainline: fields.get(1).and_then(|field| field.parse().ok()).filter(|mainline| *mainline > 0),
+        no_commit: fields.get(2) == Some(&"true"),
+    })
+}
+
+fn state_dir(repo: &Repository) -> PathBuf {
+    repo.nvcs_dir.join("sequencer")
+}
+
+fn read_state(repo: &Repository, name: &str) -> Result<Option<String>> {
+    let file = state_dir(repo).join(name);
+    if file.exists() {
+        Ok(Some(fs::read_to_string(file)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn write_state(repo: &Repository, name: &str, content: &str) -> Result<()> {
+    fs::write(state_dir(repo).join(name), content)?;
+    Ok(())
+}
+
+fn remove_state(repo: &Repository, name: &str) -> Result<()> {
+    let file = state_dir(repo).join(name);
+    if file.exists() {
+        fs::remove_file(file)?;
+    }
+    Ok(())
+}
@@ -1,12 +1,21 @@
 use anyhow::Result;
 use colored::*;
 use crate::core::Repository;
+use crate::utils::diff::{format_summary, DiffOptions, SummaryFormat};
+use crate::utils::pretty::{
+    commit_changes, commit_file_stats, commit_json, decoration_names, expand_template, ref_decorations, signature_lines, PrettyFormat,
+};
 
-pub fn execute(commit_hash: Option<String>) -> Result<()> {
+pub fn execute(
+    commit_hash: Option<String>,
+    format: PrettyFormat,
+    summary: Option<SummaryFormat>,
+    options: DiffOptions,
+) -> Result<()> {
     let repo = Repository::find_repository()?;
-    
+
     let commit_hash = match commit_hash {
-        Some(hash) => hash,
+        Some(revision) => repo.resolve_revision(&revision)?,
         None => repo.get_head()?.ok_or_else(|| anyhow::anyhow!("No commits yet"))?,
     };
 
@@ -15,54 +24,56 @@ pub fn execute(commit_hash: Option<String>) -> Result<()> {
         .ok_or_else(|| anyhow::anyhow!("Object is not a commit"))?;
 
     // Show commit info
-    println!("{} {}", "commit".yellow(), commit_hash);
-    if commit.is_merge() {
-        println!("{} {}", "Merge:".bright_white(), commit.parents.join(" "));
+    match &format {
+        PrettyFormat::Json => {
+            println!("{}", commit_json(&repo, &commit_hash, commit)?);
+            return Ok(());
+        }
+        PrettyFormat::Oneline => {
+            println!("{} {}", commit_hash[..8].yellow(), commit.message.lines().next().unwrap_or(""));
+        }
+        PrettyFormat::Template(template) => {
+            let decorations = ref_decorations(&repo)?;
+            let labels = decorations.get(&commit_hash).map(Vec::as_slice).unwrap_or_default();
+            println!("{}", expand_template(template, &commit_hash, commit, &decoration_names(labels)));
+        }
+        PrettyFormat::Medium => {
+            println!("{} {}", "commit".yellow(), commit_hash);
+            if commit.is_merge() {
+                println!("{} {}", "Merge:".bright_white(), commit.parents.join(" "));
+            }
+            for line in signature_lines(commit) {
+                println!("{}", line);
+            }
+            println!();
+            for line in commit.message.lines() {
+                println!("    {}", line);
+            }
+            println!();
+        }
     }
-    println!("{} {}", "Author:".bright_white(), commit.author);
-    println!("{} {}", "Date:".bright_white(), commit.timestamp.format("%Y-%m-%d %H:%M:%S UTC"));
-    println!();
-    for line in commit.message.lines() {
-        println!("    {}", line);
+
+    let stats = commit_file_stats(&repo, commit, &options)?;
+    if let Some(format) = summary {
+        print!("{}", format_summary(&stats, format));
+        return Ok(());
     }
-    println!();
 
-    // Show diff from parent (simplified)
-    if let Some(parent_hash) = commit.parents.first() {
-        let parent_obj = repo.load_object(parent_hash)?;
-        let parent_commit = parent_obj.as_commit().unwrap();
-        
-        let current_tree = repo.load_object(&commit.tree)?;
-        let parent_tree = repo.load_object(&parent_commit.tree)?;
-        
-        let current_tree = current_tree.as_tree().unwrap();
-        let parent_tree = parent_tree.as_tree().unwrap();
-        
-        // Simple diff showing changed files
-        for (path, entry) in &current_tree.entries {
-            if let Some(parent_entry) = parent_tree.entries.get(path) {
-                if entry.hash != parent_entry.hash {
-                    println!("{} {}", "modified:".yellow(), path);
-                }
-            } else {
-                println!("{} {}", "new file:".green(), path);
-            }
+    // Show changed files relative to the first parent (simplified)
+    for stat in &stats {
+        match stat.status {
+            // Changes hidden by the whitespace options leave nothing to show
+            'M' if stat.binary.is_none() && stat.insertions + stat.deletions == 0 => {}
+            'M' => println!("{} {}", "modified:".yellow(), stat.path),
+            'A' => println!("{} {}", "new file:".green(), stat.path),
+            _ => println!("{} {}", "deleted:".red(), stat.path),
         }
-        
-        for (path, _) in &parent_tree.entries {
-            if !current_tree.entries.contains_key(path) {
-                println!("{} {}", "deleted:".red(), path);
-            }
-        }
-    } else {
-        // Initial commit - show all files as new
-        let tree_obj = repo.load_object(&commit.tree)?;
-        let tree = tree_obj.as_tree().unwrap();
-        
-        for (path, _) in &tree.entries {
-            println!("{} {}", "new file:".green(), path);
+    }
+    for change in commit_changes(&repo, commit)? {
+        if let Some((old_mode, new_mode)) = change.mode_change() {
+            println!("{} {} => {} {}", "mode change".cyan(), old_mode.octal(), new_mode.octal(), change.path);
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
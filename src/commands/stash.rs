@@ -0,0 +1,278 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use anyhow::Result;
+use crate::core::{Repository, Object, Commit, Tree, Blob, Index, Config, FileMode};
+use crate::core::attributes::Attributes;
+use crate::core::merge::merge_tree_hashes;
+use crate::core::reflog::{self, ReflogEntry};
+use crate::core::worktree::{checkout_tree, stage_tree_changes, write_index_tree};
+use crate::utils::diff::{format_summary, DiffOptions, SummaryFormat};
+use crate::utils::files::IgnoreRules;
+
+const STASH_REF: &str = "refs/stash";
+
+/// Saves the staged and working tree changes as a stash entry and resets
+/// the working tree to HEAD.
+///
+/// An entry is a commit of the working tree whose parents are HEAD, a
+/// commit of the index and, with `--include-untracked`, a root commit of
+/// the untracked files.
+pub fn push(message: Option<String>, include_untracked: bool) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let head = repo.get_head()?
+        .ok_or_else(|| anyhow::anyhow!("You do not have the initial commit yet"))?;
+    let head_commit = repo.load_commit(&head)?;
+    let index = repo.load_index()?;
+
+    let index_tree = write_index_tree(&repo, &index)?;
+    let tracked = repo.flatten_tree(&index_tree)?;
+    let untracked = if include_untracked { untracked_files(&repo, &tracked)? } else { BTreeMap::new() };
+
+    // Working tree versions of every tracked file
+    let attributes = Attributes::load(&repo)?;
+    let mut work_files = BTreeMap::new();
+    for path in tracked.keys() {
+        let full_path = repo.root.join(path);
+        if full_path.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir()) {
+            let blob = Blob::new(attributes.read_file(path)?);
+            work_files.insert(path.clone(), repo.store_object(&Object::Blob(blob))?);
+        }
+    }
+    let work_tree = store_tree(&repo, &work_files)?;
+
+    if work_tree == head_commit.tree && index_tree == head_commit.tree && untracked.is_empty() {
+        println!("No local changes to save");
+        return Ok(());
+    }
+
+    let branch = repo.get_current_branch()?.unwrap_or_else(|| "(no branch)".to_string());
+    let description = format!("{}: {} {}", branch, &head[..8], head_commit.message.lines().next().unwrap_or(""));
+    let message = match message {
+        Some(message) => format!("On {}: {}", branch, message),
+        None => format!("WIP on {}", description),
+    };
+
+    let config = Config::load(Some(&repo))?;
+    let (author, committer) = (config.author()?, config.committer()?);
+    let index_commit = Commit::new(index_tree, vec![head.clone()], author.clone(), committer.clone(), format!("index on {}", description));
+    let mut parents = vec![head.clone(), repo.store_object(&Object::Commit(index_commit))?];
+    if !untracked.is_empty() {
+        let untracked_commit = Commit::new(
+            store_tree(&repo, &untracked)?,
+            Vec::new(),
+            author.clone(),
+            committer.clone(),
+            format!("untracked files on {}", description),
+        );
+        parents.push(repo.store_object(&Object::Commit(untracked_commit))?);
+    }
+    let stash_commit = Commit::new(work_tree.clone(), parents, author, committer, message.clone());
+    let stash_hash = repo.store_object(&Object::Commit(stash_commit))?;
+
+    let previous = repo.read_ref(STASH_REF)?;
+    repo.update_ref(STASH_REF, &stash_hash)?;
+    reflog::append(&repo, STASH_REF, previous.as_deref(), &stash_hash, &message)?;
+
+    // Back to a clean HEAD
+    checkout_tree(&repo, Some(&work_tree), &head_commit.tree)?;
+    for path in untracked.keys() {
+        let full_path = repo.root.join(path);
+        if full_path.is_file() {
+            fs::remove_file(full_path)?;
+        }
+    }
+    repo.save_index(&Index::new())?;
+
+    println!("Saved working directory and index state {}", message);
+    Ok(())
+}
+
+pub fn list() -> Result<()> {
+    let repo = Repository::find_repository()?;
+    for (number, entry) in reflog::read(&repo, STASH_REF)?.iter().enumerate() {
+        println!("stash@{{{}}}: {}", number, entry.message);
+    }
+    Ok(())
+}
+
+/// Shows the changes recorded in a stash entry against the commit it was
+/// made on, as a diffstat or with `patch` as a full diff.
+pub fn show(stash: Option<String>, patch: bool) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let (_, entry) = find_entry(&repo, stash.as_deref())?;
+    let commit = repo.load_commit(&entry.new)?;
+    let base = repo.load_commit(&commit.parents[0])?;
+
+    let old_files = repo.flatten_tree(&base.tree)?;
+    let new_files = repo.flatten_tree(&commit.tree)?;
+    let load = |hash: Option<&String>| -> Result<Vec<u8>> {
+        match hash {
+            Some(hash) => Ok(repo.load_object(hash)?.as_blob().map(|blob| blob.content.clone()).unwrap_or_default()),
+            None => Ok(Vec::new()),
+        }
+    };
+
+    let attributes = Attributes::load(&repo)?;
+    let mut stats = Vec::new();
+    let options = DiffOptions::from_config(&Config::load(Some(&repo))?)?;
+    let paths: BTreeSet<&String> = old_files.keys().chain(new_files.keys()).collect();
+    for path in paths {
+        let (old_hash, new_hash) = (old_files.get(path), new_files.get(path));
+        if old_hash == new_hash {
+            continue;
+        }
+        let (old, new) = (load(old_hash)?, load(new_hash)?);
+        let status = match (old_hash, new_hash) {
+            (None, _) => 'A',
+            (_, None) => 'D',
+            _ => 'M',
+        };
+        if patch {
+            attributes.print_diff(path, &old, &new, &options)?;
+        } else {
+            stats.push(attributes.file_stat(path, status, &old, &new, &options)?);
+        }
+    }
+    if !patch {
+        print!("{}", format_summary(&stats, SummaryFormat::Stat));
+    }
+    Ok(())
+}
+
+pub fn apply(stash: Option<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let (number, _) = find_entry(&repo, stash.as_deref())?;
+    apply_entry(&repo, number)?;
+    Ok(())
+}
+
+pub fn pop(stash: Option<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let (number, _) = find_entry(&repo, stash.as_deref())?;
+    if apply_entry(&repo, number)? {
+        drop_entry(&repo, number)
+    } else {
+        println!("The stash entry is kept in case you need it again.");
+        Ok(())
+    }
+}
+
+pub fn drop(stash: Option<String>) -> Result<()> {
+    let repo = Repository::find_repository()?;
+    let (number, _) = find_entry(&repo, stash.as_deref())?;
+    drop_entry(&repo, number)
+}
+
+/// Merges a stash entry into the working tree. Returns whether it applied
+/// without conflicts.
+fn apply_entry(repo: &Repository, number: usize) -> Result<bool> {
+    let entries = reflog::read(repo, STASH_REF)?;
+    let stash = repo.load_commit(&entries[number].new)?;
+    let base = repo.load_commit(&stash.parents[0])?;
+
+    // Untracked files come back as they were, but never over existing files
+    let untracked = match stash.parents.get(2) {
+        Some(hash) => repo.flatten_tree(&repo.load_commit(hash)?.tree)?,
+        None => BTreeMap::new(),
+    };
+    if let Some(existing) = untracked.keys().find(|path| repo.root.join(path).exists()) {
+        return Err(anyhow::anyhow!("{} already exists, no checkout", existing));
+    }
+
+    let mut index = repo.load_index()?;
+    let current_tree = write_index_tree(repo, &index)?;
+    let result = merge_tree_hashes(
+        repo,
+        &base.tree,
+        &current_tree,
+        &stash.tree,
+        &DiffOptions::from_config(&Config::load(Some(repo))?)?,
+        ("Updated upstream", "Stashed changes"),
+    )?;
+    let merged_tree = result.merged_tree.expect("merges always produce a tree");
+    checkout_tree(repo, Some(&current_tree), &merged_tree)?;
+
+    // Changes that were staged are staged again, unless the merge conflicted
+    let staged_tree = repo.load_commit(&stash.parents[1])?.tree;
+    if result.success && staged_tree != base.tree {
+        stage_tree_changes(repo, &mut index, &base.tree, &staged_tree)?;
+        repo.save_index(&index)?;
+    }
+
+    let attributes = Attributes::load(repo)?;
+    for (path, hash) in &untracked {
+        let blob_obj = repo.load_object(hash)?;
+        let full_path = repo.root.join(path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(full_path, attributes.to_worktree(path, blob_obj.as_blob().unwrap().content.clone())?)?;
+    }
+
+    for conflict in &result.conflicts {
+        println!("CONFLICT: Merge conflict in {}", conflict);
+    }
+    Ok(result.success)
+}
+
+fn drop_entry(repo: &Repository, number: usize) -> Result<()> {
+    let mut entries = reflog::read(repo, STASH_REF)?;
+    let dropped = entries.remove(number);
+    reflog::write(repo, STASH_REF, &entries)?;
+
+    match entries.first() {
+        Some(top) => repo.update_ref(STASH_REF, &top.new)?,
+        None => {
+            let ref_file = repo.nvcs_dir.join(STASH_REF);
+            if ref_file.exists() {
+                fs::remove_file(ref_file)?;
+            }
+        }
+    }
+
+    println!("Dropped stash@{{{}}} ({})", number, &dropped.new[..8]);
+    Ok(())
+}
+
+/// Looks up `stash@{n}` or `n`, defaulting to the newest entry.
+fn find_entry(repo: &Repository, stash: Option<&str>) -> Result<(usize, ReflogEntry)> {
+    let entries = reflog::read(repo, STASH_REF)?;
+    if entries.is_empty() {
+        return Err(anyhow::anyhow!("No stash entries found."));
+    }
+    let number = match stash {
+        None => 0,
+        Some(name) => name
+            .strip_prefix("stash@{")
+            .and_then(|rest| rest.strip_suffix('}'))
+            .unwrap_or(name)
+            .parse()
+            .map_err(|_| anyhow::anyhow!("'{}' is not a stash reference", name))?,
+    };
+    let entry = entries.get(number)
+        .ok_or_else(|| anyhow::anyhow!("stash@{{{}}} does not exist", number))?;
+    Ok((number, entry.clone()))
+}
+
+fn untracked_files(repo: &Repository, tracked: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+    let attributes = Attributes::load(repo)?;
+    let mut untracked = BTreeMap::new();
+    for file_path in IgnoreRules::load(repo)?.files()? {
+        let path = file_path.strip_prefix(&repo.root)?.to_string_lossy().to_string();
+        if !tracked.contains_key(&path) {
+            let blob = Blob::new(attributes.read_file(&path)?);
+            untracked.insert(path, repo.store_object(&Object::Blob(blob))?);
+        }
+    }
+    Ok(untracked)
+}
+
+/// Stores a tree of working files, keeping the mode each has on disk.
+fn store_tree(repo: &Repository, files: &BTreeMap<String, String>) -> Result<String> {
+    let mut tree = Tree::new();
+    for (path, hash) in files {
+        let mode = FileMode::of(&repo.root.join(path)).unwrap_or_default();
+        tree.add_entry(path.clone(), hash.clone(), mode);
+    }
+    repo.store_object(&Object::Tree(tree))
+}
@@ -1,14 +1,13 @@
-use std::fs;
 use std::collections::HashSet;
 use anyhow::Result;
 use colored::*;
 use crate::core::Repository;
-use crate::utils::files::{find_files_in_directory, is_ignored};
-use crate::utils::hash::calculate_file_hash;
+use crate::utils::files::IgnoreRules;
+use crate::core::attributes::Attributes;
 
 pub fn execute() -> Result<()> {
     let repo = Repository::find_repository()?;
-    let index = repo.load_index()?;
+    let mut index = repo.load_index()?;
 
     // Show current branch
     if let Some(branch) = repo.get_current_branch()? {
@@ -18,32 +17,37 @@ pub fn execute() -> Result<()> {
     }
 
     // Get all files in working directory
-    let all_files = find_files_in_directory(&repo.root)?;
+    let all_files = IgnoreRules::load(&repo)?.files()?;
     let mut working_files = HashSet::new();
     
     for file_path in all_files {
-        if !is_ignored(&file_path) {
-            if let Ok(relative_path) = file_path.strip_prefix(&repo.root) {
-                working_files.insert(relative_path.to_path_buf());
-            }
+        if let Ok(relative_path) = file_path.strip_prefix(&repo.root) {
+            working_files.insert(relative_path.to_path_buf());
         }
     }
 
     // Staged files
     let staged_files: HashSet<_> = index.entries.keys().cloned().collect();
     
-    // Modified files (staged but changed in working directory)
+    // Modified files (staged but changed in working directory), compared
+    // as they would be stored, or with a different mode. Only files whose
+    // stat data changed since they were staged are read.
+    let attributes = Attributes::load(&repo)?;
     let mut modified_files = Vec::new();
-    for (path, entry) in &index.entries {
-        let full_path = repo.root.join(path);
-        if full_path.exists() {
-            if let Ok(current_hash) = calculate_file_hash(&full_path) {
-                if current_hash != entry.hash {
-                    modified_files.push(path);
-                }
-            }
+    let present: Vec<_> = index.entries.iter()
+        .filter(|(path, entry)| !entry.removed && repo.root.join(path).symlink_metadata().is_ok())
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in present {
+        if index.is_up_to_date(&path, &repo.root, &attributes).is_ok_and(|up_to_date| !up_to_date) {
+            modified_files.push(path);
         }
     }
+    modified_files.sort();
+    if index.is_refreshed() {
+        // Status still works where the index cannot be written
+        let _ = repo.save_index(&index);
+    }
 
     // Untracked files
     let untracked_files: Vec<_> = working_files
@@ -60,7 +64,7 @@ pub fn execute() -> Result<()> {
         println!("\nChanges to be committed:");
         println!("  (use \"nvcs reset HEAD <file>...\" to unstage)");
         for file in &staged_files {
-            if deleted_files.contains(&file) {
+            if deleted_files.contains(&file) || index.entries[file].removed {
                 println!("        {}: {}", "deleted".red(), file.display());
             } else {
                 println!("        {}: {}", "new file".green(), file.display());
@@ -71,7 +75,7 @@ pub fn execute() -> Result<()> {
     if !modified_files.is_empty() {
         println!("\nChanges not staged for commit:");
         println!("  (use \"nvcs add <file>...\" to update what will be committed)");
-        for file in modified_files {
+        for file in &modified_files {
             println!("        {}: {}", "modified".yellow(), file.display());
         }
     }
@@ -79,7 +83,7 @@ pub fn execute() -> Result<()> {
     if !untracked_files.is_empty() {
         println!("\nUntracked files:");
         println!("  (use \"nvcs add <file>...\" to include in what will be committed)");
-        for file in untracked_files {
+        for file in &untracked_files {
             println!("        {}", file.display().to_string().red());
         }
     }
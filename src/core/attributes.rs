@@ -0,0 +1,565 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use anyhow::Result;
+use globset::{GlobBuilder, GlobMatcher};
+use uuid::Uuid;
+use crate::core::{Config, Repository, TreeEntry};
+use crate::utils::diff::{print_diff, text_content, DiffOptions, FileStat};
+
+/// Name of the per-directory attributes files
+pub const ATTRIBUTES_FILE: &str = ".nvcsattributes";
+
+/// The state of one attribute for a path. Attributes no line mentions are
+/// unspecified and simply absent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttrValue {
+    /// `attr`
+    Set,
+    /// `-attr`
+    Unset,
+    /// `attr=value`
+    Value(String),
+}
+
+impl fmt::Display for AttrValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttrValue::Set => f.write_str("set"),
+            AttrValue::Unset => f.write_str("unset"),
+            AttrValue::Value(value) => f.write_str(value),
+        }
+    }
+}
+
+/// How the `diff` attribute says to show changes to a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffDriver {
+    /// Unspecified: text unless the content looks binary
+    Auto,
+    /// `diff`: always as text
+    Text,
+    /// `-diff`: never as text
+    Binary,
+    /// `diff=<name>`: through the `diff.<name>.*` settings
+    Driver(String),
+}
+
+/// How the `merge` attribute says to combine changes made on both sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeDriver {
+    /// Unspecified, `merge` or `merge=text`: the line-based three-way merge
+    Text,
+    /// `-merge` or `merge=binary`: keep our version and report a conflict
+    Binary,
+    /// `merge=ours`: keep our version without a conflict
+    Ours,
+    /// `merge=union`: keep the lines of both sides instead of conflicting
+    Union,
+    /// `merge=<name>`: run the `merge.<name>.driver` command
+    Driver(String),
+}
+
+/// Line endings asked for by the `eol` attribute or core.eol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eol {
+    Lf,
+    Crlf,
+}
+
+/// The core.autocrlf setting, which converts line endings of files the
+/// attributes say nothing about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoCrlf {
+    /// Leave line endings alone
+    False,
+    /// Store text with LF and check it out with CRLF
+    True,
+    /// Store text with LF and check it out unchanged
+    Input,
+}
+
+/// Every specified attribute of one path.
+#[derive(Debug, Clone, Default)]
+pub struct PathAttributes(BTreeMap<String, AttrValue>);
+
+impl PathAttributes {
+    pub fn get(&self, name: &str) -> Option<&AttrValue> {
+        self.0.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &AttrValue)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub fn diff(&self) -> DiffDriver {
+        match self.get("diff") {
+            None => DiffDriver::Auto,
+            Some(AttrValue::Set) => DiffDriver::Text,
+            Some(AttrValue::Unset) => DiffDriver::Binary,
+            Some(AttrValue::Value(name)) => DiffDriver::Driver(name.clone()),
+        }
+    }
+
+    pub fn merge(&self) -> MergeDriver {
+        match self.get("merge") {
+            None | Some(AttrValue::Set) => MergeDriver::Text,
+            Some(AttrValue::Unset) => MergeDriver::Binary,
+            Some(AttrValue::Value(name)) => match name.as_str() {
+                "text" => MergeDriver::Text,
+                "binary" => MergeDriver::Binary,
+                "ours" => MergeDriver::Ours,
+                "union" => MergeDriver::Union,
+                _ => MergeDriver::Driver(name.clone()),
+            },
+        }
+    }
+
+    pub fn eol(&self) -> Option<Eol> {
+        match self.get("eol") {
+            Some(AttrValue::Value(value)) if value == "lf" => Some(Eol::Lf),
+            Some(AttrValue::Value(value)) if value == "crlf" => Some(Eol::Crlf),
+            _ => None,
+        }
+    }
+
+    /// The content filter driver named by `filter=<name>`.
+    pub fn filter(&self) -> Option<&str> {
+        match self.get("filter") {
+            Some(AttrValue::Value(name)) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Whether `content` counts as text: `text` says yes, `-text` says no,
+    /// and otherwise (including `text=auto`) it is guessed from the content.
+    pub fn is_text(&self, content: &[u8]) -> bool {
+        match self.get("text") {
+            Some(AttrValue::Set) => true,
+            Some(AttrValue::Unset) => false,
+            _ => text_content(content).is_some(),
+        }
+    }
+}
+
+/// One pattern line of an attributes file.
+struct Rule {
+    matcher: GlobMatcher,
+    /// Patterns without a slash match the file name at any depth
+    name_only: bool,
+    /// Changes in line order; `None` makes the attribute unspecified again
+    assignments: Vec<(String, Option<AttrValue>)>,
+}
+
+/// The attributes of the working directory: `.nvcsattributes` files, where
+/// deeper files override the ones above them, then `.nvcs/info/attributes`
+/// over all of them. Within a file, later lines win.
+pub struct Attributes {
+    root: PathBuf,
+    config: Config,
+    autocrlf: AutoCrlf,
+    /// core.eol: the ending for `text` files without an `eol` attribute
+    core_eol: Eol,
+    info: Vec<Rule>,
+    /// Each directory's rules by relative path, read on first use
+    dirs: RefCell<HashMap<String, Rc<Vec<Rule>>>>,
+    /// Whether directories missing from `dirs` are read from the working
+    /// directory, rather than having no rules
+    read_worktree: bool,
+}
+
+impl Attributes {
+    pub fn load(repo: &Repository) -> Result<Self> {
+        let config = Config::load(Some(repo))?;
+        let autocrlf = match config.get("core.autocrlf") {
+            Some(value) if value.eq_ignore_ascii_case("input") => AutoCrlf::Input,
+            _ if config.get_bool("core.autocrlf")? == Some(true) => AutoCrlf::True,
+            _ => AutoCrlf::False,
+        };
+        let core_eol = match config.get("core.eol") {
+            None | Some("lf") | Some("native") => Eol::Lf,
+            Some("crlf") => Eol::Crlf,
+            Some(value) => return Err(anyhow::anyhow!("Bad core.eol value '{}' (use lf, crlf or native)", value)),
+        };
+        Ok(Self {
+            root: repo.root.clone(),
+            config,
+            autocrlf,
+            core_eol,
+            info: parse_rules(&repo.nvcs_dir.join("info").join("attributes"))?,
+            dirs: RefCell::new(HashMap::new()),
+            read_worktree: true,
+        })
+    }
+
+    /// The attributes a checkout of the flattened tree `files` will have,
+    /// taking `.nvcsattributes` from the tree instead of the working directory.
+    pub fn from_tree(repo: &Repository, files: &BTreeMap<String, TreeEntry>) -> Result<Self> {
+        let mut dirs = HashMap::new();
+        for (path, entry) in files {
+            let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+            if name != ATTRIBUTES_FILE {
+                continue;
+            }
+            let blob_obj = repo.load_object(&entry.hash)?;
+            let content = blob_obj.as_blob().map(|blob| blob.content.clone()).unwrap_or_default();
+            let rules = parse_rule_text(&String::from_utf8_lossy(&content), path)?;
+            dirs.insert(dir.to_string(), Rc::new(rules));
+        }
+        Ok(Self {
+            read_worktree: false,
+            dirs: RefCell::new(dirs),
+            ..Self::load(repo)?
+        })
+    }
+
+    /// The attributes of `path`, relative to the repository root.
+    pub fn get(&self, path: &str) -> Result<PathAttributes> {
+        let mut values = BTreeMap::new();
+        let names: Vec<&str> = path.split('/').collect();
+        for depth in 0..names.len() {
+            let rules = self.dir_rules(&names[..depth].join("/"))?;
+            apply(&rules, &names[depth..].join("/"), &mut values);
+        }
+        apply(&self.info, path, &mut values);
+        Ok(PathAttributes(values))
+    }
+
+    fn dir_rules(&self, dir: &str) -> Result<Rc<Vec<Rule>>> {
+        if let Some(rules) = self.dirs.borrow().get(dir) {
+            return Ok(rules.clone());
+        }
+        if !self.read_worktree {
+            return Ok(Rc::new(Vec::new()));
+        }
+        let rules = Rc::new(parse_rules(&self.root.join(dir).join(ATTRIBUTES_FILE))?);
+        self.dirs.borrow_mut().insert(dir.to_string(), rules.clone());
+        Ok(rules)
+    }
+
+    /// The text to diff `content` of `path` as, or `None` to show it as
+    /// binary. A `diff=<name>` driver can mark files binary with
+    /// `diff.<name>.binary` or convert them with the `diff.<name>.textconv`
+    /// command, which gets the content in a file and prints text.
+    pub fn diff_text(&self, path: &str, content: &[u8]) -> Result<Option<String>> {
+        let guess = || text_content(content).map(str::to_string);
+        Ok(match self.get(path)?.diff() {
+            DiffDriver::Auto => guess(),
+            DiffDriver::Text => Some(String::from_utf8_lossy(content).into_owned()),
+            DiffDriver::Binary => None,
+            DiffDriver::Driver(name) => {
+                if self.config.get_bool(&format!("diff.{}.binary", name))? == Some(true) {
+                    None
+                } else if let Some(command) = self.config.get(&format!("diff.{}.textconv", name)) {
+                    // A missing side stays empty rather than going through the command
+                    if content.is_empty() {
+                        Some(String::new())
+                    } else {
+                        Some(self.textconv(command, content)?)
+                    }
+                } else {
+                    guess()
+                }
+            }
+        })
+    }
+
+    fn textconv(&self, command: &str, content: &[u8]) -> Result<String> {
+        let file = temp_file(content)?;
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$@\"", command))
+            .arg(command)
+            .arg(&file)
+            .current_dir(&self.root)
+            .output();
+        let _ = fs::remove_file(&file);
+        let output = output?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!("Text conversion '{}' failed", command));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// Line counts for a change to `path`, diffed as its attributes say.
+    pub fn file_stat(&self, path: &str, status: char, old: &[u8], new: &[u8], options: &DiffOptions) -> Result<FileStat> {
+        let (old_text, new_text) = (self.diff_text(path, old)?, self.diff_text(path, new)?);
+        let texts = old_text.as_deref().zip(new_text.as_deref());
+        FileStat::new(path, status, texts, (old.len(), new.len()), options)
+    }
+
+    /// Prints the diff of a change to `path`, or a note when it is binary.
+    pub fn print_diff(&self, path: &str, old: &[u8], new: &[u8], options: &DiffOptions) -> Result<()> {
+        match (self.diff_text(path, old)?, self.diff_text(path, new)?) {
+            (Some(old_text), Some(new_text)) => print_diff(&old_text, &new_text, path, options)?,
+            _ if old != new => println!("Binary files a/{} and b/{} differ", path, path),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The line ending `path` has in the working directory when it is text,
+    /// or `None` when its line endings are left alone. The `eol` attribute
+    /// wins; files marked `text` follow core.autocrlf, then core.eol; other
+    /// files are only converted when core.autocrlf is on.
+    fn worktree_eol(&self, attributes: &PathAttributes) -> Option<Eol> {
+        let text = attributes.get("text");
+        if text == Some(&AttrValue::Unset) {
+            return None;
+        }
+        if let Some(eol) = attributes.eol() {
+            return Some(eol);
+        }
+        match (self.autocrlf, text) {
+            (AutoCrlf::True, _) => Some(Eol::Crlf),
+            (AutoCrlf::Input, _) => Some(Eol::Lf),
+            (AutoCrlf::False, Some(_)) => Some(self.core_eol),
+            (AutoCrlf::False, None) => None,
+        }
+    }
+
+    /// Converts working directory content of `path` to what is stored: it
+    /// goes through the clean filter, then text files whose line endings
+    /// are managed get LF line endings.
+    pub fn to_repository(&self, path: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+        let attributes = self.get(path)?;
+        let content = self.run_filter(&attributes, path, "clean", content)?;
+        if self.worktree_eol(&attributes).is_some() && attributes.is_text(&content) {
+            return Ok(crlf_to_lf(&content));
+        }
+        Ok(content)
+    }
+
+    /// Reads the working file at `path` (relative to the root) as it would
+    /// be stored. A symbolic link is stored as its target, unconverted.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let full_path = self.root.join(path);
+        if fs::symlink_metadata(&full_path)?.file_type().is_symlink() {
+            return Ok(fs::read_link(&full_path)?.to_string_lossy().into_owned().into_bytes());
+        }
+        self.to_repository(path, fs::read(full_path)?)
+    }
+
+    /// Converts the stored content of `path` to what is written to the
+    /// working directory, undoing `to_repository`: text files checked out
+    /// with CRLF get it back, then the smudge filter runs.
+    pub fn to_worktree(&self, path: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+        let attributes = self.get(path)?;
+        let content = if self.worktree_eol(&attributes) == Some(Eol::Crlf) && attributes.is_text(&content) {
+            lf_to_crlf(&content)
+        } else {
+            content
+        };
+        self.run_filter(&attributes, path, "smudge", content)
+    }
+
+    /// Pipes `content` through the `filter.<name>.clean` or `.smudge`
+    /// command of the path's filter driver, with `%f` standing for the
+    /// path. Without a command, or when it fails, the content passes
+    /// through unchanged unless `filter.<name>.required` is set.
+    fn run_filter(&self, attributes: &PathAttributes, path: &str, kind: &str, content: Vec<u8>) -> Result<Vec<u8>> {
+        let Some(name) = attributes.filter() else {
+            return Ok(content);
+        };
+        let required = self.config.get_bool(&format!("filter.{}.required", name))?.unwrap_or(false);
+        let Some(command) = self.config.get(&format!("filter.{}.{}", name, kind)) else {
+            if required {
+                return Err(anyhow::anyhow!("{}: the required {} filter '{}' is not configured", path, kind, name));
+            }
+            return Ok(content);
+        };
+
+        let command = command.replace("%f", &shell_quote(path));
+        match pipe_through(&self.root, &command, content.clone()) {
+            Ok(filtered) => Ok(filtered),
+            Err(error) if required => Err(anyhow::anyhow!("{}: {} filter '{}' failed: {}", path, kind, name, error)),
+            Err(error) => {
+                eprintln!("Warning: {}: {} filter '{}' failed: {}", path, kind, name, error);
+                Ok(content)
+            }
+        }
+    }
+
+    /// Runs the `merge.<name>.driver` command on the three versions of
+    /// `path`. The command sees them as the files `%O` (base), `%A` (ours)
+    /// and `%B` (theirs), with `%P` the path, and leaves the result in `%A`,
+    /// exiting nonzero on conflicts. Returns the result and whether it is
+    /// clean, or `None` when the driver is not configured.
+    pub fn run_merge_driver(
+        &self,
+        name: &str,
+        path: &str,
+        base: &[u8],
+        ours: &[u8],
+        theirs: &[u8],
+    ) -> Result<Option<(Vec<u8>, bool)>> {
+        let Some(command) = self.config.get(&format!("merge.{}.driver", name)) else {
+            return Ok(None);
+        };
+        let files = [temp_file(base)?, temp_file(ours)?, temp_file(theirs)?];
+
+        let mut expanded = String::new();
+        let mut chars = command.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                expanded.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('O') => expanded.push_str(&shell_quote(&files[0].to_string_lossy())),
+                Some('A') => expanded.push_str(&shell_quote(&files[1].to_string_lossy())),
+                Some('B') => expanded.push_str(&shell_quote(&files[2].to_string_lossy())),
+                Some('P') => expanded.push_str(&shell_quote(path)),
+                Some('L') => expanded.push('7'),
+                Some(other) => {
+                    expanded.push('%');
+                    if other != '%' {
+                        expanded.push(other);
+                    }
+                }
+                None => expanded.push('%'),
+            }
+        }
+
+        let status = Command::new("sh").arg("-c").arg(&expanded).current_dir(&self.root).status();
+        let merged = fs::read(&files[1]);
+        for file in &files {
+            let _ = fs::remove_file(file);
+        }
+        Ok(Some((merged?, status?.success())))
+    }
+}
+
+/// Reads the rules of an attributes file; a missing file has none.
+fn parse_rules(file: &Path) -> Result<Vec<Rule>> {
+    if !file.is_file() {
+        return Ok(Vec::new());
+    }
+    parse_rule_text(&fs::read_to_string(file)?, &file.display().to_string())
+}
+
+/// Parses attribute rules from `text`, naming `source` in errors.
+fn parse_rule_text(text: &str, source: &str) -> Result<Vec<Rule>> {
+    let mut rules = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        let Some(pattern) = words.next() else {
+            continue;
+        };
+        if pattern.starts_with('#') {
+            continue;
+        }
+        if pattern.starts_with('!') {
+            eprintln!("Warning: negative patterns are ignored ({}:{})", source, number + 1);
+            continue;
+        }
+        let glob = GlobBuilder::new(pattern.trim_start_matches('/'))
+            .literal_separator(true)
+            .build()
+            .map_err(|error| anyhow::anyhow!("Bad pattern on line {} of {}: {}", number + 1, source, error))?;
+        rules.push(Rule {
+            matcher: glob.compile_matcher(),
+            name_only: !pattern.contains('/'),
+            assignments: words.flat_map(parse_assignment).collect(),
+        });
+    }
+    Ok(rules)
+}
+
+fn parse_assignment(word: &str) -> Vec<(String, Option<AttrValue>)> {
+    let (name, value) = if let Some(name) = word.strip_prefix('-') {
+        (name, Some(AttrValue::Unset))
+    } else if let Some(name) = word.strip_prefix('!') {
+        (name, None)
+    } else if let Some((name, value)) = word.split_once('=') {
+        (name, Some(AttrValue::Value(value.to_string())))
+    } else {
+        (word, Some(AttrValue::Set))
+    };
+
+    // The built-in macro: no text diff, no merge and no line-ending changes
+    if name == "binary" && value == Some(AttrValue::Set) {
+        return vec![
+            ("binary".to_string(), Some(AttrValue::Set)),
+            ("diff".to_string(), Some(AttrValue::Unset)),
+            ("merge".to_string(), Some(AttrValue::Unset)),
+            ("text".to_string(), Some(AttrValue::Unset)),
+        ];
+    }
+    vec![(name.to_string(), value)]
+}
+
+/// Applies the rules matching `path`, given relative to their file.
+fn apply(rules: &[Rule], path: &str, values: &mut BTreeMap<String, AttrValue>) {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    for rule in rules {
+        if !rule.matcher.is_match(if rule.name_only { name } else { path }) {
+            continue;
+        }
+        for (attribute, value) in &rule.assignments {
+            match value {
+                Some(value) => values.insert(attribute.clone(), value.clone()),
+                None => values.remove(attribute),
+            };
+        }
+    }
+}
+
+fn lf_to_crlf(content: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(content.len());
+    for (index, &byte) in content.iter().enumerate() {
+        if byte == b'\n' && (index == 0 || content[index - 1] != b'\r') {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+    }
+    converted
+}
+
+fn crlf_to_lf(content: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(content.len());
+    for (index, &byte) in content.iter().enumerate() {
+        if byte == b'\r' && content.get(index + 1) == Some(&b'\n') {
+            continue;
+        }
+        converted.push(byte);
+    }
+    converted
+}
+
+/// Runs `command` through the shell in `dir` with `input` on its standard
+/// input, returning its standard output.
+fn pipe_through(dir: &Path, command: &str, input: Vec<u8>) -> Result<Vec<u8>> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    // Feed the input from another thread so a full output pipe cannot block it
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    // A command that exits without reading everything closes the pipe early
+    let _ = writer.join();
+    if !output.status.success() {
+        return Err(anyhow::anyhow!("'{}' exited with {}", command, output.status));
+    }
+    Ok(output.stdout)
+}
+
+fn temp_file(content: &[u8]) -> Result<PathBuf> {
+    let path = std::env::temp_dir().join(format!("nvcs-{}", Uuid::new_v4()));
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
@@ -18,6 +18,7 @@ impl Blob {
         String::from_utf8(self.content.clone())
     }
 
+    #[allow(dead_code)]
     pub fn size(&self) -> usize {
         self.content.len()
     }
@@ -1,11 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Branch {
     pub name: String,
     pub commit: String,
 }
 
+#[allow(dead_code)]
 impl Branch {
     pub fn new(name: String, commit: String) -> Self {
         Self { name, commit }
@@ -1,22 +1,73 @@
+use std::fmt;
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use uuid::Uuid;
 
+/// Who did something and when, in their own time zone.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    /// Local time of the action, carrying its offset from UTC
+    pub when: DateTime<FixedOffset>,
+}
+
+impl Signature {
+    pub fn new(name: String, email: String, when: DateTime<FixedOffset>) -> Self {
+        Self { name, email, when }
+    }
+
+    /// Parses an identity written as `Name <email>`, `Name` or `<email>`.
+    pub fn parse(identity: &str, when: DateTime<FixedOffset>) -> Self {
+        let identity = identity.trim();
+        match identity.split_once('<') {
+            Some((name, rest)) => {
+                let email = rest.split_once('>').map(|(email, _)| email).unwrap_or(rest);
+                Self::new(name.trim().to_string(), email.trim().to_string(), when)
+            }
+            None => Self::new(identity.to_string(), String::new(), when),
+        }
+    }
+
+    /// The name and email without the date.
+    pub fn identity(&self) -> String {
+        match (self.name.is_empty(), self.email.is_empty()) {
+            (_, true) => self.name.clone(),
+            (true, false) => format!("<{}>", self.email),
+            (false, false) => format!("{} <{}>", self.name, self.email),
+        }
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        self.when.with_timezone(&Utc)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.identity())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredCommit")]
 pub struct Commit {
     pub id: String,
     pub tree: String,
     pub parents: Vec<String>,
-    pub author: String,
+    /// Who wrote the change
+    pub author: Signature,
+    /// Who recorded this commit, e.g. whoever cherry-picked or rebased it
+    pub committer: Signature,
     pub message: String,
-    pub timestamp: DateTime<Utc>,
 }
 
 impl Commit {
     pub fn new(
         tree: String,
         parents: Vec<String>,
-        author: String,
+        author: Signature,
+        committer: Signature,
         message: String,
     ) -> Self {
         Self {
@@ -24,8 +75,8 @@ impl Commit {
             tree,
             parents,
             author,
+            committer,
             message,
-            timestamp: Utc::now(),
         }
     }
 
@@ -36,4 +87,48 @@ impl Commit {
     pub fn short_hash(&self) -> String {
         self.id.chars().take(8).collect()
     }
-}
\ No newline at end of file
+
+    /// When the commit was recorded; history is ordered by this.
+    pub fn time(&self) -> DateTime<Utc> {
+        self.committer.time()
+    }
+}
+
+/// A commit as found in the object store. Commits written before authors
+/// and committers were split have a plain author string and a UTC
+/// `timestamp` instead of the two signatures.
+#[derive(Deserialize)]
+struct StoredCommit {
+    id: String,
+    tree: String,
+    parents: Vec<String>,
+    author: StoredAuthor,
+    committer: Option<Signature>,
+    message: String,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredAuthor {
+    Signature(Signature),
+    Legacy(String),
+}
+
+impl From<StoredCommit> for Commit {
+    fn from(stored: StoredCommit) -> Self {
+        let when = stored.timestamp.unwrap_or_default().fixed_offset();
+        let author = match stored.author {
+            StoredAuthor::Signature(signature) => signature,
+            StoredAuthor::Legacy(identity) => Signature::parse(&identity, when),
+        };
+        Self {
+            id: stored.id,
+            tree: stored.tree,
+            parents: stored.parents,
+            committer: stored.committer.unwrap_or_else(|| author.clone()),
+            author,
+            message: stored.message,
+        }
+    }
+}
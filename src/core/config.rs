@@ -0,0 +1,351 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Local};
+use crate::core::Repository;
+use crate::core::commit::Signature;
+use crate::utils::date::parse_signature_date;
+
+/// Where a setting comes from. Later scopes override earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigScope {
+    /// Every user on the machine (`/etc/nvcsconfig`)
+    System,
+    /// The current user (`~/.nvcsconfig`)
+    Global,
+    /// The current repository (`.nvcs/config`)
+    Local,
+}
+
+impl ConfigScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigScope::System => "system",
+            ConfigScope::Global => "global",
+            ConfigScope::Local => "local",
+        }
+    }
+
+    /// The file backing this scope. `NVCS_CONFIG_SYSTEM` and
+    /// `NVCS_CONFIG_GLOBAL` point the outer scopes elsewhere; the local scope
+    /// only exists inside a repository.
+    pub fn path(&self, repo: Option<&Repository>) -> Option<PathBuf> {
+        match self {
+            ConfigScope::System => Some(
+                std::env::var_os("NVCS_CONFIG_SYSTEM")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from("/etc/nvcsconfig")),
+            ),
+            ConfigScope::Global => std::env::var_os("NVCS_CONFIG_GLOBAL")
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".nvcsconfig"))),
+            ConfigScope::Local => repo.map(|repo| repo.nvcs_dir.join("config")),
+        }
+    }
+}
+
+/// One configuration file in the INI style of `.gitconfig`:
+///
+/// ```text
+/// [user]
+///     name = Ada Lovelace   # inline comments are allowed
+/// [branch "main"]
+///     remote = origin
+/// ```
+///
+/// Settings are kept in file order as `section[.subsection].name` keys.
+/// The file's text is kept too, so edits only touch the lines they change
+/// and comments and layout survive.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    lines: Vec<String>,
+    settings: Vec<Setting>,
+    /// Section keys with the line of their header
+    sections: Vec<(String, usize)>,
+}
+
+#[derive(Debug, Clone)]
+struct Setting {
+    key: String,
+    value: String,
+    line: usize,
+}
+
+impl ConfigFile {
+    /// Reads `path`; a missing file is simply empty.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut file = ConfigFile { path: path.to_path_buf(), ..Default::default() };
+        if path.exists() {
+            file.lines = fs::read_to_string(path)?.lines().map(str::to_string).collect();
+            file.parse()?;
+        }
+        Ok(file)
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        self.settings.clear();
+        self.sections.clear();
+        let mut section: Option<String> = None;
+        for (number, line) in self.lines.iter().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let invalid = || anyhow::anyhow!("Bad config line {} in {}", number + 1, self.path.display());
+
+            if let Some(header) = line.strip_prefix('[') {
+                let (header, rest) = header.split_once(']').ok_or_else(invalid)?;
+                if !strip_comment(rest).trim().is_empty() {
+                    return Err(invalid());
+                }
+                let header = header.trim();
+                let key = match header.split_once(char::is_whitespace) {
+                    Some((name, subsection)) => {
+                        let subsection = subsection.trim().strip_prefix('"')
+                            .and_then(|rest| rest.strip_suffix('"'))
+                            .ok_or_else(invalid)?;
+                        format!("{}.{}", name.to_ascii_lowercase(), subsection)
+                    }
+                    None => header.to_ascii_lowercase(),
+                };
+                self.sections.push((key.clone(), number));
+                section = Some(key);
+                continue;
+            }
+
+            let section = section.as_ref().ok_or_else(invalid)?;
+            let (name, value) = match line.split_once('=') {
+                Some((name, value)) => (name.trim(), parse_value(value)),
+                // A bare name is a boolean flag
+                None => (strip_comment(line).trim(), "true".to_string()),
+            };
+            if name.is_empty() {
+                return Err(invalid());
+            }
+            let key = format!("{}.{}", section, name.to_ascii_lowercase());
+            self.settings.push(Setting { key, value, line: number });
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = normalize_key(key).ok()?;
+        self.settings.iter().rev().find(|setting| setting.key == key).map(|setting| setting.value.as_str())
+    }
+
+    /// Every setting as a key and value, in file order.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.settings.iter().map(|setting| (setting.key.as_str(), setting.value.as_str()))
+    }
+
+    /// Replaces the value of `key` on its line, or adds it at the end of its
+    /// section, starting the section when there is none.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let key = normalize_key(key)?;
+        let section = section_of(&key);
+        let name = &key[section.len() + 1..];
+
+        if let Some(setting) = self.settings.iter().rev().find(|setting| setting.key == key) {
+            let line = &self.lines[setting.line];
+            let indent = &line[..line.len() - line.trim_start().len()];
+            let written = strip_comment(line.split('=').next().unwrap_or_default()).trim();
+            self.lines[setting.line] = format!("{}{} = {}", indent, written, quote(value));
+        } else {
+            let last_line = self.settings.iter()
+                .filter(|setting| section_of(&setting.key) == section)
+                .map(|setting| setting.line)
+                .chain(self.sections.iter().filter(|(key, _)| key == section).map(|(_, line)| *line))
+                .max();
+            let line = format!("\t{} = {}", name, quote(value));
+            match last_line {
+                Some(last_line) => self.lines.insert(last_line + 1, line),
+                None => {
+                    self.lines.push(match section.split_once('.') {
+                        Some((name, subsection)) => format!("[{} \"{}\"]", name, subsection),
+                        None => format!("[{}]", section),
+                    });
+                    self.lines.push(line);
+                }
+            }
+        }
+        self.parse()
+    }
+
+    /// Removes every value of `key` and its line. Returns whether there was one.
+    pub fn unset(&mut self, key: &str) -> Result<bool> {
+        let key = normalize_key(key)?;
+        let lines: Vec<usize> = self.settings.iter()
+            .filter(|setting| setting.key == key)
+            .map(|setting| setting.line)
+            .collect();
+        for line in lines.iter().rev() {
+            self.lines.remove(*line);
+        }
+        self.parse()?;
+        Ok(!lines.is_empty())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let content: String = self.lines.iter().map(|line| format!("{}\n", line)).collect();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, content)?;
+        Ok(())
+    }
+}
+
+/// Every configuration scope, layered so later scopes win.
+#[derive(Debug, Default)]
+pub struct Config {
+    files: Vec<(ConfigScope, ConfigFile)>,
+    /// When new commits are made, taken once so author and committer agree
+    now: DateTime<FixedOffset>,
+}
+
+impl Config {
+    /// Reads the system, global and (with a repository) local files.
+    pub fn load(repo: Option<&Repository>) -> Result<Self> {
+        let mut files = Vec::new();
+        for scope in [ConfigScope::System, ConfigScope::Global, ConfigScope::Local] {
+            if let Some(path) = scope.path(repo) {
+                files.push((scope, ConfigFile::load(&path)?));
+            }
+        }
+        Ok(Config { files, now: Local::now().fixed_offset() })
+    }
+
+    /// Like `load`, for the repository around the current directory if any.
+    pub fn load_current() -> Result<Self> {
+        let repo = Repository::find_repository().ok();
+        Self::load(repo.as_ref())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.files.iter().rev().find_map(|(_, file)| file.get(key))
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        self.get(key)
+            .map(|value| parse_bool(value).ok_or_else(|| anyhow::anyhow!("Bad boolean value '{}' for {}", value, key)))
+            .transpose()
+    }
+
+    /// Every setting with its scope, lowest precedence first.
+    pub fn entries(&self) -> impl Iterator<Item = (ConfigScope, &str, &str)> {
+        self.files.iter().flat_map(|(scope, file)| {
+            file.entries().map(move |(key, value)| (*scope, key, value))
+        })
+    }
+
+    /// The author of new commits: user.name and user.email, overridden by
+    /// `NVCS_AUTHOR_NAME`, `NVCS_AUTHOR_EMAIL` and `NVCS_AUTHOR_DATE`.
+    pub fn author(&self) -> Result<Signature> {
+        self.signature("AUTHOR")
+    }
+
+    /// Like `author`, with the `NVCS_COMMITTER_*` variables.
+    pub fn committer(&self) -> Result<Signature> {
+        self.signature("COMMITTER")
+    }
+
+    fn signature(&self, role: &str) -> Result<Signature> {
+        let variable = |field: &str| std::env::var(format!("NVCS_{}_{}", role, field)).ok();
+        let name = variable("NAME")
+            .or_else(|| self.get("user.name").map(str::to_string))
+            .unwrap_or_else(|| "Unknown".to_string());
+        let email = variable("EMAIL")
+            .or_else(|| self.get("user.email").map(str::to_string))
+            .unwrap_or_default();
+        let when = match variable("DATE") {
+            Some(date) => parse_signature_date(&date)?,
+            None => self.now,
+        };
+        Ok(Signature::new(name, email, when))
+    }
+
+    /// Whether color.ui forces colors on or off; `None` leaves it to the terminal.
+    pub fn color_ui(&self) -> Result<Option<bool>> {
+        match self.get("color.ui") {
+            None | Some("auto") => Ok(None),
+            Some("always") => Ok(Some(true)),
+            Some("never") => Ok(Some(false)),
+            Some(_) => self.get_bool("color.ui"),
+        }
+    }
+}
+
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
+/// Lowercases the section and name of a key, keeping the subsection's case.
+pub fn normalize_key(key: &str) -> Result<String> {
+    let invalid = || anyhow::anyhow!("Key '{}' does not contain a section and a name", key);
+    let (section, rest) = key.split_once('.').ok_or_else(invalid)?;
+    let (subsection, name) = match rest.rsplit_once('.') {
+        Some((subsection, name)) => (Some(subsection), name),
+        None => (None, rest),
+    };
+    if section.is_empty() || name.is_empty() {
+        return Err(invalid());
+    }
+    Ok(match subsection {
+        Some(subsection) => format!("{}.{}.{}", section.to_ascii_lowercase(), subsection, name.to_ascii_lowercase()),
+        None => format!("{}.{}", section.to_ascii_lowercase(), name.to_ascii_lowercase()),
+    })
+}
+
+fn section_of(key: &str) -> &str {
+    key.rsplit_once('.').map(|(section, _)| section).unwrap_or(key)
+}
+
+/// Reads the value after `=`: quoted parts are kept as they are, apart
+/// from `\"` and `\\` escapes, while an unquoted `#` or `;` starts a comment
+/// and whitespace around the value is dropped.
+fn parse_value(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    // Length of the value up to its last quoted or non-blank character
+    let mut end = 0;
+    let mut chars = raw.trim_start().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => value.extend(chars.next()),
+            '#' | ';' if !quoted => break,
+            c => value.push(c),
+        }
+        if quoted || c == '"' || !c.is_whitespace() {
+            end = value.len();
+        }
+    }
+    value.truncate(end);
+    value
+}
+
+/// Drops an unquoted `#` or `;` comment from the end of `text`.
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' | ';' if !quoted => return &text[..index],
+            _ => {}
+        }
+    }
+    text
+}
+
+fn quote(value: &str) -> String {
+    if value.trim() != value || value.contains(['#', ';', '"']) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use std::process::Command;
+use anyhow::Result;
+use crate::core::{Config, Repository};
+
+/// Where hooks live: core.hooksPath (relative to the repository root), or
+/// `.nvcs/hooks`.
+fn hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    let config = Config::load(Some(repo))?;
+    Ok(match config.get("core.hookspath") {
+        Some(path) => repo.root.join(path),
+        None => repo.nvcs_dir.join("hooks"),
+    })
+}
+
+/// Runs the hook `name` from the repository root with `args`, if it exists.
+/// Hooks see `NVCS_DIR` and `NVCS_INDEX_FILE` in their environment. Returns
+/// an error when the hook exits with a nonzero status, so `pre-*` and
+/// `commit-msg` hooks can stop an operation.
+///
+/// The hooks run are `pre-commit`, `commit-msg <message file>` and
+/// `post-commit` by `commit`; `pre-merge` by `merge`;
+/// `post-checkout <old> <new> 1` by `checkout`; and
+/// `post-reset <old> <new> <mode>` by `reset`.
+pub fn run(repo: &Repository, name: &str, args: &[&str]) -> Result<()> {
+    let hook = hooks_dir(repo)?.join(name);
+    if !hook.is_file() {
+        return Ok(());
+    }
+    if !is_executable(&hook) {
+        eprintln!("hint: The '{}' hook was ignored because it's not set as executable.", name);
+        return Ok(());
+    }
+
+    let status = Command::new(&hook)
+        .args(args)
+        .current_dir(&repo.root)
+        .env("NVCS_DIR", &repo.nvcs_dir)
+        .env("NVCS_INDEX_FILE", &repo.index_file)
+        .status()
+        .map_err(|error| anyhow::anyhow!("Cannot run the {} hook: {}", name, error))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("The {} hook failed ({})", name, status));
+    }
+    Ok(())
+}
+
+/// Like `run`, for hooks that run after the fact: a failure is reported
+/// but does not fail the command.
+pub fn run_post(repo: &Repository, name: &str, args: &[&str]) {
+    if let Err(error) = run(repo, name, args) {
+        eprintln!("warning: {}", error);
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &std::path::Path) -> bool {
+    true
+}
@@ -1,27 +1,110 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use crate::utils::hash::calculate_file_hash;
+use crate::core::{Repository, Object, Blob, FileMode};
+use crate::core::attributes::Attributes;
+use crate::utils::hash::calculate_hash;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IndexEntry {
     pub path: PathBuf,
     pub hash: String,
+    #[serde(flatten)]
+    pub stat: StatData,
+    /// Stages the path's deletion; `hash` is empty
+    #[serde(default)]
+    pub removed: bool,
+    /// Indexes written before modes existed only staged regular files
+    #[serde(default)]
+    pub mode: FileMode,
+}
+
+impl IndexEntry {
+    /// An entry staging the deletion of `path`.
+    pub fn removal(path: PathBuf) -> Self {
+        Self { path, hash: String::new(), stat: StatData::default(), removed: true, mode: FileMode::default() }
+    }
+}
+
+/// The stat data of a working file when it was last known to hold its
+/// staged content. While the file's stat data stays the same it is trusted
+/// to be unchanged without reading it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct StatData {
     pub size: u64,
+    /// Modification time in seconds; 0 when the stat data is unknown
     pub modified: i64,
+    #[serde(default)]
+    pub modified_nanos: u32,
+    /// Status change time in seconds
+    #[serde(default)]
+    pub changed: i64,
+    #[serde(default)]
+    pub changed_nanos: u32,
+    #[serde(default)]
+    pub inode: u64,
+}
+
+impl StatData {
+    /// The stat data in `metadata`, as read with `symlink_metadata`.
+    pub fn of(metadata: &fs::Metadata) -> Self {
+        let (modified, modified_nanos) = metadata.modified().map(since_epoch).unwrap_or_default();
+        let (changed, changed_nanos, inode) = change_time_and_inode(metadata);
+        Self { size: metadata.len(), modified, modified_nanos, changed, changed_nanos, inode }
+    }
+
+    /// The stat data to record for a file whose content matching the index
+    /// was read at `read_at`. A file modified in that same second could be
+    /// changed again without its stat data changing on file systems with
+    /// coarse timestamps, so its stat data stays unknown and it is always
+    /// reread.
+    pub fn recorded(metadata: &fs::Metadata, read_at: SystemTime) -> Self {
+        let stat = Self::of(metadata);
+        if stat.modified >= since_epoch(read_at).0 {
+            return Self::default();
+        }
+        stat
+    }
+
+    /// Whether `metadata` shows a file untouched since this was recorded.
+    pub fn matches(&self, metadata: &fs::Metadata) -> bool {
+        self.modified != 0 && *self == Self::of(metadata)
+    }
+}
+
+fn since_epoch(time: SystemTime) -> (i64, u32) {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| (duration.as_secs() as i64, duration.subsec_nanos()))
+        .unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn change_time_and_inode(metadata: &fs::Metadata) -> (i64, u32, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.ctime(), metadata.ctime_nsec() as u32, metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn change_time_and_inode(_metadata: &fs::Metadata) -> (i64, u32, u64) {
+    (0, 0, 0)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Index {
     pub entries: HashMap<PathBuf, IndexEntry>,
+    /// Whether `is_up_to_date` recorded new stat data worth saving
+    #[serde(skip)]
+    refreshed: bool,
 }
 
 impl Index {
     pub fn new() -> Self {
         Self {
             entries: HashMap::new(),
+            refreshed: false,
         }
     }
 
@@ -46,41 +129,94 @@ impl Index {
         Ok(())
     }
 
-    pub fn add_file<P: AsRef<Path>>(&mut self, file_path: P, repo_root: P) -> Result<()> {
+    /// Stages the file with its mode and its content as stored, after the
+    /// filters and line-ending conversion its `attributes` ask for. A
+    /// symbolic link is staged as its target, not the file it points to.
+    /// A file whose stat data shows it untouched since it was staged is
+    /// not read again.
+    pub fn add_file<P: AsRef<Path>>(&mut self, repo: &Repository, file_path: P, attributes: &Attributes) -> Result<()> {
         let file_path = file_path.as_ref();
-        let repo_root = repo_root.as_ref();
-        
-        if !file_path.exists() {
+
+        let Ok(metadata) = fs::symlink_metadata(file_path) else {
             return Err(anyhow::anyhow!("File does not exist: {}", file_path.display()));
-        }
+        };
 
-        let relative_path = file_path.strip_prefix(repo_root)?;
-        let metadata = fs::metadata(file_path)?;
-        let hash = calculate_file_hash(file_path)?;
+        let relative_path = file_path.strip_prefix(&repo.root)?;
+        let mode = FileMode::from_metadata(&metadata);
+        if self.entries.get(relative_path).is_some_and(|entry| entry.mode == mode && entry.stat.matches(&metadata)) {
+            return Ok(());
+        }
+        let content = attributes.read_file(&relative_path.to_string_lossy())?;
+        let stat = StatData::recorded(&metadata, SystemTime::now());
+        let hash = repo.store_object(&Object::Blob(Blob::new(content)))?;
 
         let entry = IndexEntry {
             path: relative_path.to_path_buf(),
             hash,
-            size: metadata.len(),
-            modified: metadata.modified()?.duration_since(std::time::UNIX_EPOCH)?.as_secs() as i64,
+            stat,
+            removed: false,
+            mode,
         };
 
         self.entries.insert(relative_path.to_path_buf(), entry);
         Ok(())
     }
 
+    /// Whether the working file at `path` still holds the staged content
+    /// with the staged mode. Unchanged stat data is trusted; otherwise the
+    /// file is rehashed, and when it still matches its new stat data is
+    /// recorded so the next check can skip it.
+    pub fn is_up_to_date(&mut self, path: &Path, repo_root: &Path, attributes: &Attributes) -> Result<bool> {
+        let Some(entry) = self.entries.get_mut(path) else {
+            return Ok(false);
+        };
+        let Ok(metadata) = fs::symlink_metadata(repo_root.join(path)) else {
+            return Ok(false);
+        };
+        if FileMode::from_metadata(&metadata) != entry.mode {
+            return Ok(false);
+        }
+        if entry.stat.matches(&metadata) {
+            return Ok(true);
+        }
+
+        let content = attributes.read_file(&path.to_string_lossy())?;
+        if calculate_hash(&content) != entry.hash {
+            return Ok(false);
+        }
+        let stat = StatData::recorded(&metadata, SystemTime::now());
+        if stat != entry.stat {
+            entry.stat = stat;
+            self.refreshed = true;
+        }
+        Ok(true)
+    }
+
+    /// Whether `is_up_to_date` recorded stat data that saving would keep.
+    pub fn is_refreshed(&self) -> bool {
+        self.refreshed
+    }
+
     pub fn remove_file<P: AsRef<Path>>(&mut self, file_path: P) {
         self.entries.remove(file_path.as_ref());
     }
 
+    /// Stages the deletion of `file_path`, so the next commit drops it.
+    pub fn stage_removal<P: AsRef<Path>>(&mut self, file_path: P) {
+        let path = file_path.as_ref().to_path_buf();
+        self.entries.insert(path.clone(), IndexEntry::removal(path));
+    }
+
     pub fn is_staged<P: AsRef<Path>>(&self, file_path: P) -> bool {
         self.entries.contains_key(file_path.as_ref())
     }
 
+    #[allow(dead_code)]
     pub fn get_staged_files(&self) -> Vec<&PathBuf> {
         self.entries.keys().collect()
     }
 
+    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.entries.clear();
     }
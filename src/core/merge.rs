@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::ops::Range;
 use anyhow::Result;
-use crate::core::{Repository, Object, Commit, Tree};
+use similar::DiffOp;
+use crate::core::{Repository, Object, Tree, TreeEntry, Blob, FileMode};
+use crate::core::attributes::{Attributes, MergeDriver};
+use crate::utils::diff::{diff_lines, text_content, DiffOptions};
 
 pub struct MergeResult {
     pub success: bool,
     pub conflicts: Vec<String>,
+    /// The merged tree; conflicting text files hold conflict markers
     pub merged_tree: Option<String>,
 }
 
@@ -13,6 +17,7 @@ pub fn merge_commits(
     base_commit: &str,
     our_commit: &str,
     their_commit: &str,
+    options: &DiffOptions,
 ) -> Result<MergeResult> {
     let base_obj = repo.load_object(base_commit)?;
     let our_obj = repo.load_object(our_commit)?;
@@ -26,7 +31,32 @@ pub fn merge_commits(
     let our_tree = repo.load_object(&our_commit.tree)?;
     let their_tree = repo.load_object(&their_commit.tree)?;
 
-    merge_trees(repo, base_tree.as_tree().unwrap(), our_tree.as_tree().unwrap(), their_tree.as_tree().unwrap())
+    merge_trees(
+        repo,
+        base_tree.as_tree().unwrap(),
+        our_tree.as_tree().unwrap(),
+        their_tree.as_tree().unwrap(),
+        options,
+        ("ours", "theirs"),
+    )
+}
+
+/// Three-way merges trees by hash, labelling conflict markers with `labels`.
+pub fn merge_tree_hashes(
+    repo: &Repository,
+    base_tree: &str,
+    our_tree: &str,
+    their_tree: &str,
+    options: &DiffOptions,
+    labels: (&str, &str),
+) -> Result<MergeResult> {
+    let load = |hash: &str| -> Result<Tree> {
+        repo.load_object(hash)?
+            .as_tree()
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Object {} is not a tree", hash))
+    };
+    merge_trees(repo, &load(base_tree)?, &load(our_tree)?, &load(their_tree)?, options, labels)
 }
 
 fn merge_trees(
@@ -34,7 +64,10 @@ fn merge_trees(
     base_tree: &Tree,
     our_tree: &Tree,
     their_tree: &Tree,
+    options: &DiffOptions,
+    labels: (&str, &str),
 ) -> Result<MergeResult> {
+    let attributes = Attributes::load(repo)?;
     let mut merged_tree = Tree::new();
     let mut conflicts = Vec::new();
 
@@ -56,64 +89,280 @@ fn merge_trees(
         let their_entry = their_tree.entries.get(&path);
 
         match (base_entry, our_entry, their_entry) {
-            // File unchanged in both branches
-            (Some(base), Some(our), Some(their)) if our.hash == base.hash && their.hash == base.hash => {
-                merged_tree.add_entry(path, base.hash.clone(), base.is_file);
+            // File unchanged in both branches, or changed the same way
+            (_, Some(our), Some(their)) if same(our, their) => {
+                merged_tree.add_entry(path, our.hash.clone(), our.mode);
             }
             // File changed only in our branch
-            (Some(_), Some(our), Some(their)) if their.hash == base_entry.unwrap().hash => {
-                merged_tree.add_entry(path, our.hash.clone(), our.is_file);
+            (Some(base), Some(our), Some(their)) if same(their, base) => {
+                merged_tree.add_entry(path, our.hash.clone(), our.mode);
             }
             // File changed only in their branch
-            (Some(_), Some(our), Some(their)) if our.hash == base_entry.unwrap().hash => {
-                merged_tree.add_entry(path, their.hash.clone(), their.is_file);
+            (Some(base), Some(our), Some(their)) if same(our, base) => {
+                merged_tree.add_entry(path, their.hash.clone(), their.mode);
             }
-            // File changed in both branches - conflict
-            (Some(_), Some(our), Some(their)) if our.hash != their.hash => {
-                conflicts.push(path.clone());
-                // For now, take our version
-                merged_tree.add_entry(path, our.hash.clone(), our.is_file);
+            // File changed (or added) in both branches - merge the contents
+            // line by line and the modes separately
+            (base, Some(our), Some(their)) => {
+                let (merged_hash, clean) = if our.hash == their.hash {
+                    (our.hash.clone(), true)
+                } else {
+                    merge_blobs(repo, &attributes, &path, base, our, their, options, labels)?
+                };
+                let (mode, mode_clean) = merge_mode(base.map(|base| base.mode), our.mode, their.mode);
+                if !clean {
+                    conflicts.push(match base {
+                        Some(_) => path.clone(),
+                        None => format!("{} (added on both sides)", path),
+                    });
+                } else if !mode_clean {
+                    conflicts.push(format!("{} (mode conflict)", path));
+                }
+                merged_tree.add_entry(path, merged_hash, mode);
             }
+            // File deleted on one side and untouched on the other
+            (Some(base), None, Some(their)) if same(their, base) => {}
+            (Some(base), Some(our), None) if same(our, base) => {}
             // File added in our branch only
             (None, Some(our), None) => {
-                merged_tree.add_entry(path, our.hash.clone(), our.is_file);
+                merged_tree.add_entry(path, our.hash.clone(), our.mode);
             }
             // File added in their branch only
             (None, None, Some(their)) => {
-                merged_tree.add_entry(path, their.hash.clone(), their.is_file);
+                merged_tree.add_entry(path, their.hash.clone(), their.mode);
             }
             // File deleted in our branch
             (Some(_), None, Some(their)) => {
-                // Conflict: deleted vs modified
+                // Conflict: deleted vs modified, keep the modified file
                 conflicts.push(format!("{} (deleted vs modified)", path));
+                merged_tree.add_entry(path, their.hash.clone(), their.mode);
             }
             // File deleted in their branch
             (Some(_), Some(our), None) => {
-                // Conflict: modified vs deleted
+                // Conflict: modified vs deleted, keep the modified file
                 conflicts.push(format!("{} (modified vs deleted)", path));
+                merged_tree.add_entry(path, our.hash.clone(), our.mode);
             }
-            _ => {
-                // Other cases - take our version if available, otherwise their version
-                if let Some(our) = our_entry {
-                    merged_tree.add_entry(path, our.hash.clone(), our.is_file);
-                } else if let Some(their) = their_entry {
-                    merged_tree.add_entry(path, their.hash.clone(), their.is_file);
-                }
-            }
+            // Deleted in both branches
+            (_, None, None) => {}
         }
     }
 
-    let success = conflicts.is_empty();
-    let merged_tree_hash = if success {
-        let tree_obj = Object::Tree(merged_tree);
-        Some(repo.store_object(&tree_obj)?)
-    } else {
-        None
-    };
+    conflicts.sort();
+    let tree_obj = Object::Tree(merged_tree);
+    let merged_tree_hash = repo.store_object(&tree_obj)?;
 
     Ok(MergeResult {
-        success,
+        success: conflicts.is_empty(),
         conflicts,
-        merged_tree: merged_tree_hash,
+        merged_tree: Some(merged_tree_hash),
     })
-}
\ No newline at end of file
+}
+
+/// Strips the description merge adds to non-content conflicts.
+pub fn conflict_path(conflict: &str) -> &str {
+    conflict.split(" (").next().unwrap_or(conflict)
+}
+
+/// Whether two entries have the same contents and mode.
+fn same(a: &TreeEntry, b: &TreeEntry) -> bool {
+    a.hash == b.hash && a.mode == b.mode
+}
+
+/// Three-way merges the modes of a path, keeping our mode when the sides
+/// changed it differently. Returns the mode and whether it merged cleanly.
+fn merge_mode(base: Option<FileMode>, ours: FileMode, theirs: FileMode) -> (FileMode, bool) {
+    if ours == theirs || base == Some(theirs) {
+        (ours, true)
+    } else if base == Some(ours) {
+        (theirs, true)
+    } else {
+        (ours, false)
+    }
+}
+
+/// Three-way merges the contents of a file changed on both sides (or added
+/// on both sides when there is no base), storing the merged blob. Returns
+/// the blob's hash and whether the merge was clean; overlapping changes
+/// leave conflict markers, and binary files and symbolic links keep our
+/// version. The path's `merge` attribute can pick another way to merge.
+#[allow(clippy::too_many_arguments)]
+fn merge_blobs(
+    repo: &Repository,
+    attributes: &Attributes,
+    path: &str,
+    base: Option<&TreeEntry>,
+    our: &TreeEntry,
+    their: &TreeEntry,
+    options: &DiffOptions,
+    labels: (&str, &str),
+) -> Result<(String, bool)> {
+    // Link targets are not merged
+    if our.mode == FileMode::Symlink || their.mode == FileMode::Symlink {
+        return Ok((our.hash.clone(), false));
+    }
+
+    let load = |entry: &TreeEntry| -> Result<Vec<u8>> {
+        let blob_obj = repo.load_object(&entry.hash)?;
+        Ok(blob_obj.as_blob().map(|blob| blob.content.clone()).unwrap_or_default())
+    };
+    let base = match base {
+        Some(base) => load(base)?,
+        None => Vec::new(),
+    };
+    let (ours, theirs) = (load(our)?, load(their)?);
+
+    let driver = attributes.get(path)?.merge();
+    match &driver {
+        MergeDriver::Binary => return Ok((our.hash.clone(), false)),
+        MergeDriver::Ours => return Ok((our.hash.clone(), true)),
+        MergeDriver::Driver(name) => {
+            // An unconfigured driver falls back to the text merge
+            if let Some((merged, clean)) = attributes.run_merge_driver(name, path, &base, &ours, &theirs)? {
+                return Ok((repo.store_object(&Object::Blob(Blob::new(merged)))?, clean));
+            }
+        }
+        MergeDriver::Text | MergeDriver::Union => {}
+    }
+
+    let (Some(base), Some(ours), Some(theirs)) = (text_content(&base), text_content(&ours), text_content(&theirs)) else {
+        return Ok((our.hash.clone(), false));
+    };
+
+    let union = driver == MergeDriver::Union;
+    let merged = merge_contents(base, ours, theirs, options, labels, union)?;
+    let hash = repo.store_object(&Object::Blob(Blob::from_string(merged.content)))?;
+    Ok((hash, merged.conflicts == 0))
+}
+
+pub struct ContentMerge {
+    pub content: String,
+    pub conflicts: usize,
+}
+
+/// A run of base lines replaced by one side.
+struct Change<'a> {
+    base: Range<usize>,
+    lines: &'a [&'a str],
+}
+
+fn changes<'a>(ops: &[DiffOp], new_lines: &'a [&'a str]) -> Vec<Change<'a>> {
+    ops.iter()
+        .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+        .map(|op| Change { base: op.old_range(), lines: &new_lines[op.new_range()] })
+        .collect()
+}
+
+/// Line-based three-way merge of `ours` and `theirs` against `base`.
+/// Overlapping changes that differ are wrapped in conflict markers labelled
+/// with `labels`, or with `union` kept one after the other.
+pub fn merge_contents(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    options: &DiffOptions,
+    labels: (&str, &str),
+    union: bool,
+) -> Result<ContentMerge> {
+    let our_diff = diff_lines(base, ours, options)?;
+    let their_diff = diff_lines(base, theirs, options)?;
+    let base_lines = &our_diff.old_lines;
+    let our_changes = changes(&our_diff.ops, &our_diff.new_lines);
+    let their_changes = changes(&their_diff.ops, &their_diff.new_lines);
+
+    // Lines neither side changed are copied from ours, which keeps any
+    // edits the diff options told us to ignore
+    let mut our_line_for_base = vec![None; base_lines.len()];
+    for op in &our_diff.ops {
+        if let DiffOp::Equal { old_index, new_index, len } = *op {
+            for offset in 0..len {
+                our_line_for_base[old_index + offset] = Some(our_diff.new_lines[new_index + offset]);
+            }
+        }
+    }
+    let unchanged = |range: Range<usize>| -> String {
+        range.map(|index| our_line_for_base[index].unwrap_or(base_lines[index])).collect()
+    };
+
+    let mut content = String::new();
+    let mut conflicts = 0;
+    let (mut i, mut j, mut position) = (0, 0, 0);
+
+    while i < our_changes.len() || j < their_changes.len() {
+        // Start a region at the earliest pending change, then grow it while
+        // changes from either side overlap it
+        let start = match (our_changes.get(i), their_changes.get(j)) {
+            (Some(ours), Some(theirs)) => ours.base.start.min(theirs.base.start),
+            (Some(ours), None) => ours.base.start,
+            (None, Some(theirs)) => theirs.base.start,
+            (None, None) => break,
+        };
+        let mut end = start;
+        let (first_ours, first_theirs) = (i, j);
+        loop {
+            let overlaps = |change: &Change| {
+                change.base.start < end || (change.base.start == end && (start == end || change.base.is_empty()))
+            };
+            if our_changes.get(i).is_some_and(overlaps) {
+                end = end.max(our_changes[i].base.end);
+                i += 1;
+            } else if their_changes.get(j).is_some_and(overlaps) {
+                end = end.max(their_changes[j].base.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        content.push_str(&unchanged(position..start));
+        position = end;
+
+        let side = |changes: &[Change]| -> String {
+            let mut text = String::new();
+            let mut cursor = start;
+            for change in changes {
+                text.extend(base_lines[cursor..change.base.start].iter().copied());
+                text.extend(change.lines.iter().copied());
+                cursor = change.base.end;
+            }
+            text.extend(base_lines[cursor..end].iter().copied());
+            text
+        };
+        let ours_region = &our_changes[first_ours..i];
+        let theirs_region = &their_changes[first_theirs..j];
+
+        if theirs_region.is_empty() {
+            content.push_str(&side(ours_region));
+        } else if ours_region.is_empty() {
+            content.push_str(&side(theirs_region));
+        } else {
+            let (our_text, their_text) = (side(ours_region), side(theirs_region));
+            let same = our_text.split_inclusive('\n').map(|line| options.normalize(line))
+                .eq(their_text.split_inclusive('\n').map(|line| options.normalize(line)));
+            if same {
+                content.push_str(&our_text);
+            } else if union {
+                push_terminated(&mut content, &our_text);
+                content.push_str(&their_text);
+            } else {
+                conflicts += 1;
+                content.push_str(&format!("<<<<<<< {}\n", labels.0));
+                push_terminated(&mut content, &our_text);
+                content.push_str("=======\n");
+                push_terminated(&mut content, &their_text);
+                content.push_str(&format!(">>>>>>> {}\n", labels.1));
+            }
+        }
+    }
+
+    content.push_str(&unchanged(position..base_lines.len()));
+    Ok(ContentMerge { content, conflicts })
+}
+
+fn push_terminated(content: &mut String, text: &str) {
+    content.push_str(text);
+    if !text.is_empty() && !text.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
@@ -6,11 +6,21 @@ pub mod tree;
 pub mod blob;
 pub mod branch;
 pub mod merge;
+pub mod revwalk;
+pub mod worktree;
+pub mod reflog;
+pub mod config;
+pub mod hooks;
+pub mod attributes;
 
 pub use repository::Repository;
+#[allow(unused_imports)]
 pub use object::{Object, ObjectType};
-pub use index::{Index, IndexEntry};
-pub use commit::Commit;
-pub use tree::{Tree, TreeEntry};
+pub use index::{Index, IndexEntry, StatData};
+pub use commit::{Commit, Signature};
+pub use config::Config;
+#[allow(unused_imports)]
+pub use tree::{Tree, TreeEntry, TreeChange, ChangeKind, FileMode};
 pub use blob::Blob;
+#[allow(unused_imports)]
 pub use branch::Branch;
\ No newline at end of file
@@ -3,6 +3,7 @@ use anyhow::Result;
 use crate::core::{Commit, Tree, Blob};
 use crate::utils::hash::calculate_hash;
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ObjectType {
     Commit,
@@ -18,6 +19,7 @@ pub enum Object {
 }
 
 impl Object {
+    #[allow(dead_code)]
     pub fn object_type(&self) -> ObjectType {
         match self {
             Object::Commit(_) => ObjectType::Commit,
@@ -38,6 +40,11 @@ impl Object {
     }
 
     pub fn hash(&self) -> String {
+        // Blobs are addressed by their raw content so that index entries,
+        // which hash files directly, refer to the stored blob
+        if let Object::Blob(blob) = self {
+            return calculate_hash(&blob.content);
+        }
         let data = self.serialize().unwrap();
         calculate_hash(&data)
     }
@@ -0,0 +1,79 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use crate::core::Repository;
+
+/// Stands in for the old value when a ref is first created.
+pub const ZERO_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// One recorded update of a ref.
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub old: String,
+    pub new: String,
+    pub timestamp: DateTime<Utc>,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, ' ');
+        let old = fields.next()?.to_string();
+        let new = fields.next()?.to_string();
+        let timestamp = Utc.timestamp_opt(fields.next()?.parse().ok()?, 0).single()?;
+        let message = fields.next().unwrap_or("").to_string();
+        Some(Self { old, new, timestamp, message })
+    }
+
+    fn to_line(&self) -> String {
+        format!("{} {} {} {}", self.old, self.new, self.timestamp.timestamp(), self.message)
+    }
+}
+
+fn log_file(repo: &Repository, ref_name: &str) -> PathBuf {
+    repo.nvcs_dir.join("logs").join(ref_name)
+}
+
+/// Records that `ref_name` moved from `old` to `new`.
+pub fn append(repo: &Repository, ref_name: &str, old: Option<&str>, new: &str, message: &str) -> Result<()> {
+    let file = log_file(repo, ref_name);
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let entry = ReflogEntry {
+        old: old.unwrap_or(ZERO_HASH).to_string(),
+        new: new.to_string(),
+        timestamp: Utc::now(),
+        message: message.lines().next().unwrap_or("").to_string(),
+    };
+    let mut log = OpenOptions::new().create(true).append(true).open(file)?;
+    writeln!(log, "{}", entry.to_line())?;
+    Ok(())
+}
+
+/// Reads the log of `ref_name`, newest entry first.
+pub fn read(repo: &Repository, ref_name: &str) -> Result<Vec<ReflogEntry>> {
+    let file = log_file(repo, ref_name);
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    let mut entries: Vec<ReflogEntry> = fs::read_to_string(file)?.lines().filter_map(ReflogEntry::parse).collect();
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Replaces the log of `ref_name` with `entries`, given newest first.
+pub fn write(repo: &Repository, ref_name: &str, entries: &[ReflogEntry]) -> Result<()> {
+    let file = log_file(repo, ref_name);
+    if entries.is_empty() {
+        if file.exists() {
+            fs::remove_file(file)?;
+        }
+        return Ok(());
+    }
+    let content: String = entries.iter().rev().map(|entry| entry.to_line() + "\n").collect();
+    fs::write(file, content)?;
+    Ok(())
+}
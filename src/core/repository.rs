@@ -1,8 +1,8 @@
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::fs;
 use anyhow::{Result, Context};
-use crate::core::{Index, Object, Commit, Tree, Blob, Branch};
-use crate::utils::hash::calculate_hash;
+use crate::core::{Index, Object, Commit, TreeEntry};
 
 pub struct Repository {
     pub root: PathBuf,
@@ -32,20 +32,21 @@ impl Repository {
         }
     }
 
-    pub fn init(&self) -> Result<()> {
+    /// Creates the repository with HEAD on the unborn `default_branch`.
+    pub fn init(&self, default_branch: &str) -> Result<()> {
         if self.nvcs_dir.exists() {
             return Err(anyhow::anyhow!("Repository already exists"));
         }
 
         fs::create_dir_all(&self.objects_dir)
             .context("Failed to create objects directory")?;
-        fs::create_dir_all(&self.refs_dir.join("heads"))
+        fs::create_dir_all(self.refs_dir.join("heads"))
             .context("Failed to create refs/heads directory")?;
-        fs::create_dir_all(&self.refs_dir.join("tags"))
+        fs::create_dir_all(self.refs_dir.join("tags"))
             .context("Failed to create refs/tags directory")?;
 
-        // Initialize HEAD to point to main branch
-        fs::write(&self.head_file, "ref: refs/heads/main\n")
+        // Initialize HEAD to point to the default branch
+        fs::write(&self.head_file, format!("ref: refs/heads/{}\n", default_branch))
             .context("Failed to create HEAD file")?;
 
         // Create empty index
@@ -94,6 +95,10 @@ impl Repository {
         Ok(hash)
     }
 
+    pub fn has_object(&self, hash: &str) -> bool {
+        hash.len() > 2 && self.objects_dir.join(&hash[..2]).join(&hash[2..]).exists()
+    }
+
     pub fn load_object(&self, hash: &str) -> Result<Object> {
         let (dir, file) = hash.split_at(2);
         let object_file = self.objects_dir.join(dir).join(file);
@@ -106,6 +111,129 @@ impl Repository {
         Object::deserialize(&data)
     }
 
+    pub fn load_commit(&self, hash: &str) -> Result<Commit> {
+        match self.load_object(hash)? {
+            Object::Commit(commit) => Ok(commit),
+            _ => Err(anyhow::anyhow!("Object {} is not a commit", hash)),
+        }
+    }
+
+    /// Lists every file under a tree as full path to blob hash, descending
+    /// into subtrees.
+    pub fn flatten_tree(&self, hash: &str) -> Result<BTreeMap<String, String>> {
+        Ok(self.flatten_tree_entries(hash)?
+            .into_iter()
+            .map(|(path, entry)| (path, entry.hash))
+            .collect())
+    }
+
+    /// Like `flatten_tree`, keeping each file's mode. Entries are named by
+    /// their full path.
+    pub fn flatten_tree_entries(&self, hash: &str) -> Result<BTreeMap<String, TreeEntry>> {
+        let mut files = BTreeMap::new();
+        let mut pending = vec![(String::new(), hash.to_string())];
+        while let Some((prefix, tree_hash)) = pending.pop() {
+            let tree = match self.load_object(&tree_hash)? {
+                Object::Tree(tree) => tree,
+                _ => return Err(anyhow::anyhow!("Object {} is not a tree", tree_hash)),
+            };
+            for (name, entry) in tree.entries {
+                let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+                if entry.is_file() {
+                    files.insert(path.clone(), TreeEntry { name: path, ..entry });
+                } else {
+                    pending.push((path, entry.hash));
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Reads a ref such as `refs/heads/main`, returning `None` if it is unset.
+    pub fn read_ref(&self, ref_name: &str) -> Result<Option<String>> {
+        let ref_file = self.nvcs_dir.join(ref_name);
+        if !ref_file.is_file() {
+            return Ok(None);
+        }
+        let hash = fs::read_to_string(&ref_file)?.trim().to_string();
+        Ok(if hash.is_empty() { None } else { Some(hash) })
+    }
+
+    /// Resolves a revision to a commit hash. Accepts `HEAD`, branch and tag
+    /// names, full refs, full or abbreviated hashes, followed by any number
+    /// of `~N` (Nth first-parent ancestor) and `^N` (Nth parent) suffixes.
+    pub fn resolve_revision(&self, revision: &str) -> Result<String> {
+        let base_end = revision.find(['~', '^']).unwrap_or(revision.len());
+        let (name, mut suffixes) = revision.split_at(base_end);
+
+        let mut hash = self.resolve_name(name)?
+            .ok_or_else(|| anyhow::anyhow!("Unknown revision '{}'", revision))?;
+
+        while let Some(operator) = suffixes.chars().next() {
+            let digits: String = suffixes[1..].chars().take_while(|c| c.is_ascii_digit()).collect();
+            suffixes = &suffixes[1 + digits.len()..];
+            let count: usize = if digits.is_empty() { 1 } else { digits.parse()? };
+
+            if operator == '~' {
+                for _ in 0..count {
+                    let commit = self.load_commit(&hash)?;
+                    hash = commit.parents.first().cloned()
+                        .ok_or_else(|| anyhow::anyhow!("Revision '{}' goes past the root commit", revision))?;
+                }
+            } else if count > 0 {
+                let commit = self.load_commit(&hash)?;
+                hash = commit.parents.get(count - 1).cloned()
+                    .ok_or_else(|| anyhow::anyhow!("Revision '{}' has no parent {}", revision, count))?;
+            }
+        }
+
+        Ok(hash)
+    }
+
+    fn resolve_name(&self, name: &str) -> Result<Option<String>> {
+        if name == "HEAD" || name == "@" {
+            return self.get_head();
+        }
+        for ref_name in [
+            name.to_string(),
+            format!("refs/heads/{}", name),
+            format!("refs/tags/{}", name),
+        ] {
+            if ref_name.starts_with("refs/") {
+                if let Some(hash) = self.read_ref(&ref_name)? {
+                    return Ok(Some(hash));
+                }
+            }
+        }
+        self.expand_hash(name)
+    }
+
+    /// Expands a full or abbreviated object hash, failing if it is ambiguous.
+    pub fn expand_hash(&self, prefix: &str) -> Result<Option<String>> {
+        if prefix.len() < 4 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(None);
+        }
+        let prefix = prefix.to_ascii_lowercase();
+        let (dir, rest) = prefix.split_at(2);
+        let object_dir = self.objects_dir.join(dir);
+        if !object_dir.is_dir() {
+            return Ok(None);
+        }
+
+        let mut matches = Vec::new();
+        for entry in fs::read_dir(&object_dir)? {
+            let file_name = entry?.file_name().to_string_lossy().to_string();
+            if file_name.starts_with(rest) {
+                matches.push(format!("{}{}", dir, file_name));
+            }
+        }
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(matches.pop()),
+            _ => Err(anyhow::anyhow!("Short hash '{}' is ambiguous", prefix)),
+        }
+    }
+
     pub fn get_head(&self) -> Result<Option<String>> {
         if !self.head_file.exists() {
             return Ok(None);
@@ -154,6 +282,17 @@ impl Repository {
         Ok(())
     }
 
+    /// Points the current branch, or a detached HEAD, at `commit_hash`.
+    pub fn update_head(&self, commit_hash: &str) -> Result<()> {
+        match self.get_current_branch()? {
+            Some(branch) => self.update_ref(&format!("refs/heads/{}", branch), commit_hash),
+            None => {
+                fs::write(&self.head_file, format!("{}\n", commit_hash))?;
+                Ok(())
+            }
+        }
+    }
+
     pub fn list_branches(&self) -> Result<Vec<String>> {
         let heads_dir = self.refs_dir.join("heads");
         if !heads_dir.exists() {
@@ -173,6 +312,25 @@ impl Repository {
         Ok(branches)
     }
 
+    pub fn list_tags(&self) -> Result<Vec<String>> {
+        let tags_dir = self.refs_dir.join("tags");
+        if !tags_dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut tags = Vec::new();
+        for entry in fs::read_dir(&tags_dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                if let Some(name) = entry.file_name().to_str() {
+                    tags.push(name.to_string());
+                }
+            }
+        }
+        tags.sort();
+        Ok(tags)
+    }
+
     pub fn create_branch(&self, name: &str, commit_hash: &str) -> Result<()> {
         let branch_file = self.refs_dir.join("heads").join(name);
         if branch_file.exists() {
@@ -0,0 +1,187 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use crate::core::{Repository, Commit};
+
+/// Order in which a walk returns commits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WalkOrder {
+    /// Newest commit first, by commit timestamp
+    #[default]
+    Date,
+    /// Newest first, but never a parent before all of its children
+    DateOrder,
+    /// Parents after children, keeping each line of history together
+    Topo,
+}
+
+/// Walks the commit graph from a set of starting commits, skipping anything
+/// reachable from the hidden commits (`log main ^release`).
+pub struct RevWalk<'a> {
+    repo: &'a Repository,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    pub first_parent: bool,
+    pub order: WalkOrder,
+}
+
+impl<'a> RevWalk<'a> {
+    pub fn new(repo: &'a Repository) -> Self {
+        Self {
+            repo,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            first_parent: false,
+            order: WalkOrder::default(),
+        }
+    }
+
+    pub fn push(&mut self, hash: String) {
+        self.include.push(hash);
+    }
+
+    pub fn hide(&mut self, hash: String) {
+        self.exclude.push(hash);
+    }
+
+    /// Returns the selected commits with their hashes, in walk order.
+    pub fn commits(&self) -> Result<Vec<(String, Commit)>> {
+        let hidden = ancestors(self.repo, &self.exclude, false)?;
+
+        // Collect everything reachable from the starting points
+        let mut commits: HashMap<String, Commit> = HashMap::new();
+        let mut stack: Vec<String> = self.include.iter().rev().cloned().collect();
+        while let Some(hash) = stack.pop() {
+            if hidden.contains(&hash) || commits.contains_key(&hash) {
+                continue;
+            }
+            let commit = self.repo.load_commit(&hash)?;
+            stack.extend(self.parents(&commit).iter().rev().cloned());
+            commits.insert(hash, commit);
+        }
+
+        let order = match self.order {
+            WalkOrder::Date => {
+                let mut hashes: Vec<String> = commits.keys().cloned().collect();
+                hashes.sort_by(|a, b| commits[b].time().cmp(&commits[a].time()).then_with(|| a.cmp(b)));
+                hashes
+            }
+            WalkOrder::DateOrder | WalkOrder::Topo => self.topological(&commits),
+        };
+
+        Ok(order
+            .into_iter()
+            .map(|hash| {
+                let commit = commits[&hash].clone();
+                (hash, commit)
+            })
+            .collect())
+    }
+
+    fn parents<'c>(&self, commit: &'c Commit) -> &'c [String] {
+        if self.first_parent {
+            &commit.parents[..commit.parents.len().min(1)]
+        } else {
+            &commit.parents
+        }
+    }
+
+    /// Emits each commit once all of its children have been emitted. Date
+    /// order picks the newest ready commit; topo order continues down the
+    /// most recently emitted line first.
+    fn topological(&self, commits: &HashMap<String, Commit>) -> Vec<String> {
+        let mut pending_children: HashMap<&str, usize> = HashMap::new();
+        for commit in commits.values() {
+            for parent in self.parents(commit) {
+                if commits.contains_key(parent) {
+                    *pending_children.entry(parent.as_str()).or_default() += 1;
+                }
+            }
+        }
+
+        let mut tips: Vec<&str> = commits
+            .keys()
+            .map(String::as_str)
+            .filter(|hash| !pending_children.contains_key(hash))
+            .collect();
+        tips.sort_by(|a, b| commits[*a].time().cmp(&commits[*b].time()).then_with(|| b.cmp(a)));
+
+        let mut output = Vec::with_capacity(commits.len());
+        let mut heap: BinaryHeap<(DateTime<Utc>, &str)> = BinaryHeap::new();
+        let mut stack: Vec<&str> = Vec::new();
+        for tip in tips {
+            match self.order {
+                WalkOrder::Topo => stack.push(tip),
+                _ => heap.push((commits[tip].time(), tip)),
+            }
+        }
+
+        loop {
+            let next = match self.order {
+                WalkOrder::Topo => stack.pop(),
+                _ => heap.pop().map(|(_, hash)| hash),
+            };
+            let Some(hash) = next else { break };
+            output.push(hash.to_string());
+
+            // Push parents in reverse so the first parent is visited first
+            for parent in self.parents(&commits[hash]).iter().rev() {
+                let Some(count) = pending_children.get_mut(parent.as_str()) else { continue };
+                *count -= 1;
+                if *count == 0 {
+                    match self.order {
+                        WalkOrder::Topo => stack.push(parent),
+                        _ => heap.push((commits[parent].time(), parent)),
+                    }
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Returns every commit reachable from `starts`, including the starts.
+pub fn ancestors(repo: &Repository, starts: &[String], first_parent: bool) -> Result<HashSet<String>> {
+    let mut seen = HashSet::new();
+    let mut stack: Vec<String> = starts.to_vec();
+    while let Some(hash) = stack.pop() {
+        if !seen.insert(hash.clone()) {
+            continue;
+        }
+        let commit = repo.load_commit(&hash)?;
+        if first_parent {
+            stack.extend(commit.parents.first().cloned());
+        } else {
+            stack.extend(commit.parents.iter().cloned());
+        }
+    }
+    Ok(seen)
+}
+
+/// Finds the best common ancestor of `a` and `b`: a commit reachable from
+/// both that is not an ancestor of another such commit. Returns `None` for
+/// unrelated histories.
+pub fn merge_base(repo: &Repository, a: &str, b: &str) -> Result<Option<String>> {
+    let from_a = ancestors(repo, &[a.to_string()], false)?;
+    let common: HashSet<String> = ancestors(repo, &[b.to_string()], false)?
+        .into_iter()
+        .filter(|hash| from_a.contains(hash))
+        .collect();
+
+    // Anything below another common ancestor is not the best one
+    let mut parents = Vec::new();
+    for hash in &common {
+        parents.extend(repo.load_commit(hash)?.parents);
+    }
+    let older = ancestors(repo, &parents, false)?;
+
+    let mut best: Option<(DateTime<Utc>, String)> = None;
+    for hash in common.into_iter().filter(|hash| !older.contains(hash)) {
+        let timestamp = repo.load_commit(&hash)?.time();
+        if best.as_ref().is_none_or(|(best_time, best_hash)| (timestamp, &hash) > (*best_time, best_hash)) {
+            best = Some((timestamp, hash));
+        }
+    }
+    Ok(best.map(|(_, hash)| hash))
+}
@@ -1,37 +1,216 @@
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
 use serde::{Deserialize, Serialize};
 
+/// What kind of entry a tree or index entry is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileMode {
+    #[default]
+    Regular,
+    Executable,
+    /// A symbolic link; its blob holds the link target
+    Symlink,
+    Directory,
+}
+
+impl FileMode {
+    /// The mode of the file at `path`, without following symbolic links.
+    pub fn of(path: &Path) -> std::io::Result<Self> {
+        Ok(Self::from_metadata(&fs::symlink_metadata(path)?))
+    }
+
+    /// The mode described by `metadata`, as read with `symlink_metadata`.
+    pub fn from_metadata(metadata: &fs::Metadata) -> Self {
+        let file_type = metadata.file_type();
+        if file_type.is_symlink() {
+            FileMode::Symlink
+        } else if file_type.is_dir() {
+            FileMode::Directory
+        } else if is_executable(metadata) {
+            FileMode::Executable
+        } else {
+            FileMode::Regular
+        }
+    }
+
+    /// The mode as git writes it, e.g. `100755`.
+    pub fn octal(&self) -> &'static str {
+        match self {
+            FileMode::Regular => "100644",
+            FileMode::Executable => "100755",
+            FileMode::Symlink => "120000",
+            FileMode::Directory => "040000",
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredTreeEntry", into = "StoredTreeEntry")]
 pub struct TreeEntry {
     pub name: String,
     pub hash: String,
-    pub is_file: bool,
+    pub mode: FileMode,
+}
+
+impl TreeEntry {
+    pub fn is_file(&self) -> bool {
+        self.mode != FileMode::Directory
+    }
+}
+
+/// A tree entry as stored. Trees written before modes existed only say
+/// whether an entry is a file; regular files and directories are still
+/// stored that way so those trees keep their hashes.
+#[derive(Serialize, Deserialize)]
+struct StoredTreeEntry {
+    name: String,
+    hash: String,
+    is_file: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mode: Option<FileMode>,
+}
+
+impl From<StoredTreeEntry> for TreeEntry {
+    fn from(stored: StoredTreeEntry) -> Self {
+        let mode = match (stored.mode, stored.is_file) {
+            (Some(mode), _) => mode,
+            (None, true) => FileMode::Regular,
+            (None, false) => FileMode::Directory,
+        };
+        Self { name: stored.name, hash: stored.hash, mode }
+    }
+}
+
+impl From<TreeEntry> for StoredTreeEntry {
+    fn from(entry: TreeEntry) -> Self {
+        let mode = match entry.mode {
+            FileMode::Executable | FileMode::Symlink => Some(entry.mode),
+            FileMode::Regular | FileMode::Directory => None,
+        };
+        let is_file = entry.is_file();
+        Self { name: entry.name, hash: entry.hash, is_file, mode }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl ChangeKind {
+    /// Single-letter status as shown by `--name-status`.
+    pub fn letter(&self) -> char {
+        match self {
+            ChangeKind::Added => 'A',
+            ChangeKind::Modified => 'M',
+            ChangeKind::Deleted => 'D',
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TreeChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+    pub old_mode: Option<FileMode>,
+    pub new_mode: Option<FileMode>,
+}
+
+impl TreeChange {
+    /// The old and new modes when a file that exists on both sides changed mode.
+    pub fn mode_change(&self) -> Option<(FileMode, FileMode)> {
+        match (self.old_mode, self.new_mode) {
+            (Some(old), Some(new)) if old != new => Some((old, new)),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tree {
-    pub entries: HashMap<String, TreeEntry>,
+    pub entries: BTreeMap<String, TreeEntry>,
 }
 
 impl Tree {
     pub fn new() -> Self {
         Self {
-            entries: HashMap::new(),
+            entries: BTreeMap::new(),
         }
     }
 
-    pub fn add_entry(&mut self, name: String, hash: String, is_file: bool) {
-        let entry = TreeEntry { name: name.clone(), hash, is_file };
+    pub fn add_entry(&mut self, name: String, hash: String, mode: FileMode) {
+        let entry = TreeEntry { name: name.clone(), hash, mode };
         self.entries.insert(name, entry);
     }
 
+    #[allow(dead_code)]
     pub fn get_files(&self) -> Vec<&TreeEntry> {
-        self.entries.values().filter(|entry| entry.is_file).collect()
+        self.entries.values().filter(|entry| entry.is_file()).collect()
     }
 
+    #[allow(dead_code)]
     pub fn get_directories(&self) -> Vec<&TreeEntry> {
-        self.entries.values().filter(|entry| !entry.is_file).collect()
+        self.entries.values().filter(|entry| !entry.is_file()).collect()
+    }
+
+    /// Lists the entries that differ between `old` and this tree, sorted by path.
+    pub fn changes_from(&self, old: &Tree) -> Vec<TreeChange> {
+        let mut changes = Vec::new();
+
+        for (path, entry) in &self.entries {
+            match old.entries.get(path) {
+                Some(old_entry) if old_entry.hash == entry.hash && old_entry.mode == entry.mode => {}
+                Some(old_entry) => changes.push(TreeChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Modified,
+                    old_hash: Some(old_entry.hash.clone()),
+                    new_hash: Some(entry.hash.clone()),
+                    old_mode: Some(old_entry.mode),
+                    new_mode: Some(entry.mode),
+                }),
+                None => changes.push(TreeChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Added,
+                    old_hash: None,
+                    new_hash: Some(entry.hash.clone()),
+                    old_mode: None,
+                    new_mode: Some(entry.mode),
+                }),
+            }
+        }
+
+        for (path, old_entry) in &old.entries {
+            if !self.entries.contains_key(path) {
+                changes.push(TreeChange {
+                    path: path.clone(),
+                    kind: ChangeKind::Deleted,
+                    old_hash: Some(old_entry.hash.clone()),
+                    new_hash: None,
+                    old_mode: Some(old_entry.mode),
+                    new_mode: None,
+                });
+            }
+        }
+
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes
     }
 }
 
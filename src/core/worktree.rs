@@ -0,0 +1,234 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use crate::core::{Repository, Index, IndexEntry, StatData, Object, Tree, TreeEntry, Blob, FileMode};
+use crate::core::attributes::Attributes;
+use crate::utils::hash::{calculate_file_hash, calculate_hash};
+
+/// Builds the tree for the next commit: HEAD's files with the staged
+/// entries laid over them and the staged deletions removed. Returns the
+/// stored tree's hash.
+pub fn write_index_tree(repo: &Repository, index: &Index) -> Result<String> {
+    let head_tree = match repo.get_head()? {
+        Some(head) => Some(repo.load_commit(&head)?.tree),
+        None => None,
+    };
+    write_tree_with_index(repo, head_tree.as_deref(), index)
+}
+
+/// Builds a tree from the files of `base` with the staged entries laid over
+/// them and the staged deletions removed. Returns the stored tree's hash.
+pub fn write_tree_with_index(repo: &Repository, base: Option<&str>, index: &Index) -> Result<String> {
+    let mut files: BTreeMap<String, (String, FileMode)> = match base {
+        Some(tree) => repo.flatten_tree_entries(tree)?
+            .into_iter()
+            .map(|(path, entry)| (path, (entry.hash, entry.mode)))
+            .collect(),
+        None => BTreeMap::new(),
+    };
+
+    let attributes = Attributes::load(repo)?;
+    for (path, entry) in &index.entries {
+        if entry.removed {
+            files.remove(path.to_string_lossy().as_ref());
+            continue;
+        }
+        // Prefer the staged blob; older indexes only recorded the file hash
+        let blob_hash = if repo.has_object(&entry.hash) {
+            entry.hash.clone()
+        } else {
+            let content = attributes.read_file(&path.to_string_lossy())?;
+            repo.store_object(&Object::Blob(Blob::new(content)))?
+        };
+        files.insert(path.to_string_lossy().to_string(), (blob_hash, entry.mode));
+    }
+
+    let mut tree = Tree::new();
+    for (path, (hash, mode)) in files {
+        tree.add_entry(path, hash, mode);
+    }
+    repo.store_object(&Object::Tree(tree))
+}
+
+/// Lists the files of the HEAD commit as path to blob hash; empty before
+/// the first commit.
+pub fn head_files(repo: &Repository) -> Result<BTreeMap<String, String>> {
+    match repo.get_head()? {
+        Some(head) => repo.flatten_tree(&repo.load_commit(&head)?.tree),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+/// Updates the working directory from the files of tree `from` to those of
+/// tree `to`, refusing to overwrite local changes to any file it touches.
+pub fn checkout_tree(repo: &Repository, from: Option<&str>, to: &str) -> Result<()> {
+    update_worktree(repo, from, to, false)
+}
+
+/// Like `checkout_tree`, but discards local changes to the files it touches.
+pub fn reset_worktree(repo: &Repository, from: Option<&str>, to: &str) -> Result<()> {
+    update_worktree(repo, from, to, true)
+}
+
+/// Stages every file that differs between trees `from` and `to` with its
+/// version in `to`, and the deletion of files only in `from`.
+pub fn stage_tree_changes(repo: &Repository, index: &mut Index, from: &str, to: &str) -> Result<()> {
+    let old_files = repo.flatten_tree_entries(from)?;
+    let new_files = repo.flatten_tree_entries(to)?;
+    for path in old_files.keys().filter(|path| !new_files.contains_key(*path)) {
+        index.stage_removal(path);
+    }
+    for (path, entry) in new_files {
+        if old_files.get(&path).is_some_and(|old| old.hash == entry.hash && old.mode == entry.mode) {
+            continue;
+        }
+        let relative_path = PathBuf::from(&path);
+        // The working file was not read, so its stat data cannot be trusted
+        index.entries.insert(relative_path.clone(), IndexEntry {
+            path: relative_path,
+            hash: entry.hash,
+            stat: StatData::default(),
+            removed: false,
+            mode: entry.mode,
+        });
+    }
+    Ok(())
+}
+
+fn update_worktree(repo: &Repository, from: Option<&str>, to: &str, force: bool) -> Result<()> {
+    let old_files = match from {
+        Some(tree) => repo.flatten_tree_entries(tree)?,
+        None => BTreeMap::new(),
+    };
+    let new_files = repo.flatten_tree_entries(to)?;
+    // Files are written as the target tree's attributes say, which may not
+    // match the ones currently in the working directory
+    let current = Attributes::load(repo)?;
+    let attributes = Attributes::from_tree(repo, &new_files)?;
+
+    let mut updates = Vec::new();
+    let mut conflicts = Vec::new();
+    for path in old_files.keys().chain(new_files.keys()) {
+        let (old_entry, new_entry) = (old_files.get(path), new_files.get(path));
+        let unchanged = match (old_entry, new_entry) {
+            (Some(old), Some(new)) => old.hash == new.hash && old.mode == new.mode,
+            _ => false,
+        };
+        if unchanged || updates.iter().any(|(updated, _)| updated == path) {
+            continue;
+        }
+        let (old_hash, new_hash) = (old_entry.map(|entry| &entry.hash), new_entry.map(|entry| &entry.hash));
+        if !force
+            && !is_checked_out(repo, &current, path, old_hash)?
+            && !is_checked_out(repo, &attributes, path, new_hash)?
+        {
+            conflicts.push(path.clone());
+        }
+        updates.push((path.clone(), new_entry.cloned()));
+    }
+
+    if !conflicts.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Your local changes to the following files would be overwritten:\n  {}\nCommit or discard them first",
+            conflicts.join("\n  ")
+        ));
+    }
+
+    for (path, entry) in updates {
+        let full_path = repo.root.join(&path);
+        match entry {
+            Some(entry) => write_file(repo, &attributes, &path, &entry)?,
+            None => {
+                if full_path.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir()) {
+                    fs::remove_file(&full_path)?;
+                }
+                remove_empty_parents(&repo.root, &full_path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the blob of `entry` to `path` in the working directory: as a
+/// symbolic link to the target it holds, or as a file with its mode.
+fn write_file(repo: &Repository, attributes: &Attributes, path: &str, entry: &TreeEntry) -> Result<()> {
+    let blob_obj = repo.load_object(&entry.hash)?;
+    let blob = blob_obj.as_blob()
+        .ok_or_else(|| anyhow::anyhow!("Object {} is not a blob", entry.hash))?;
+    let full_path = repo.root.join(path);
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Replace whatever is there rather than writing through a link
+    if full_path.symlink_metadata().is_ok_and(|metadata| !metadata.is_dir()) {
+        fs::remove_file(&full_path)?;
+    }
+
+    if entry.mode == FileMode::Symlink {
+        return make_symlink(&String::from_utf8_lossy(&blob.content), &full_path);
+    }
+    fs::write(&full_path, attributes.to_worktree(path, blob.content.clone())?)?;
+    set_executable(&full_path, entry.mode == FileMode::Executable)
+}
+
+#[cfg(unix)]
+fn make_symlink(target: &str, path: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, path)?;
+    Ok(())
+}
+
+/// Without symbolic links, the link is checked out as a file holding its target.
+#[cfg(not(unix))]
+fn make_symlink(target: &str, path: &Path) -> Result<()> {
+    fs::write(path, target)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path, executable: bool) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    // Whoever may read the file may also run it, as with `chmod +x`
+    let mode = if executable { mode | (mode & 0o444) >> 2 } else { mode & !0o111 };
+    if mode != permissions.mode() {
+        permissions.set_mode(mode);
+        fs::set_permissions(path, permissions)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path, _executable: bool) -> Result<()> {
+    Ok(())
+}
+
+/// Whether the working file at `path` holds blob `hash` as checkout would
+/// write it, or is missing when there is no blob.
+fn is_checked_out(repo: &Repository, attributes: &Attributes, path: &str, hash: Option<&String>) -> Result<bool> {
+    let full_path = repo.root.join(path);
+    let metadata = full_path.symlink_metadata().ok().filter(|metadata| !metadata.is_dir());
+    let (hash, metadata) = match (hash, metadata) {
+        (Some(hash), Some(metadata)) => (hash, metadata),
+        (hash, metadata) => return Ok(hash.is_none() && metadata.is_none()),
+    };
+    if metadata.is_file() && calculate_file_hash(&full_path)? == *hash {
+        return Ok(true);
+    }
+    // The file may differ from the blob only by its conversions, and a
+    // link is compared by its target
+    Ok(calculate_hash(&attributes.read_file(path)?) == *hash)
+}
+
+/// Removes directories left empty by a deletion, up to the repository root.
+fn remove_empty_parents(root: &Path, path: &Path) {
+    let mut dir = path.parent();
+    while let Some(current) = dir {
+        if current == root || fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand};
 use anyhow::Result;
 
 mod commands;
@@ -6,6 +6,14 @@ mod core;
 mod utils;
 
 use commands::*;
+use commands::config::ConfigAction;
+use commands::sequencer::SequenceOptions;
+use core::Config;
+use core::config::ConfigScope;
+use core::revwalk::WalkOrder;
+use utils::alias::{self, Expansion};
+use utils::pretty::PrettyFormat;
+use utils::diff::{DiffAlgorithm, DiffOptions, SummaryFormat, WordDiffMode};
 
 #[derive(Parser)]
 #[command(name = "nvcs")]
@@ -30,23 +38,75 @@ enum Commands {
     },
     /// Commit staged changes
     Commit {
-        /// Commit message
+        /// Commit message (opens the editor when omitted)
         #[arg(short, long)]
-        message: String,
-        /// Author name
+        message: Option<String>,
+        /// Author as "Name <email>" (defaults to user.name and user.email)
         #[arg(short, long)]
         author: Option<String>,
+        /// Replace the HEAD commit instead of adding a new one
+        #[arg(long)]
+        amend: bool,
+        /// Edit the commit message in the editor
+        #[arg(short, long)]
+        edit: bool,
+        /// Create a "fixup!" commit for `rebase --autosquash` to fold into REV
+        #[arg(long, value_name = "REV", conflicts_with_all = ["amend", "squash"])]
+        fixup: Option<String>,
+        /// Create a "squash!" commit for `rebase --autosquash` to meld into REV
+        #[arg(long, value_name = "REV", conflicts_with = "amend")]
+        squash: Option<String>,
+        /// Skip the pre-commit and commit-msg hooks
+        #[arg(short = 'n', long)]
+        no_verify: bool,
     },
     /// Show repository status
     Status,
     /// Show commit history
     Log {
+        /// Revisions to start from (defaults to HEAD); prefix with ^ to exclude
+        revisions: Vec<String>,
         /// Number of commits to show
         #[arg(short, long)]
         count: Option<usize>,
         /// Show one line per commit
         #[arg(long)]
         oneline: bool,
+        /// Output format: medium, oneline, json or format:<template> (%h %an %ae %ad %cn %cd %s ...)
+        #[arg(long, visible_alias = "format", value_name = "FORMAT", conflicts_with = "oneline")]
+        pretty: Option<PrettyFormat>,
+        #[command(flatten)]
+        summary: SummaryArgs,
+        /// Show no parent before all its children, otherwise by date
+        #[arg(long, conflicts_with = "topo_order")]
+        date_order: bool,
+        /// Show no parent before all its children, keeping lines of history together
+        #[arg(long)]
+        topo_order: bool,
+        /// Follow only the first parent of merge commits
+        #[arg(long)]
+        first_parent: bool,
+        /// Draw the commit graph next to the log
+        #[arg(long)]
+        graph: bool,
+        /// Show branch and tag names next to the commits they point at
+        #[arg(long)]
+        decorate: bool,
+        /// Show only commits whose author matches a regex
+        #[arg(long, value_name = "PATTERN")]
+        author: Option<String>,
+        /// Show only commits whose message matches a regex
+        #[arg(long, value_name = "PATTERN")]
+        grep: Option<String>,
+        /// Show commits newer than a date (e.g. 2024-03-01, "2 weeks ago")
+        #[arg(long, visible_alias = "after", value_name = "DATE")]
+        since: Option<String>,
+        /// Show commits older than a date
+        #[arg(long, visible_alias = "before", value_name = "DATE")]
+        until: Option<String>,
+        /// Show only commits that changed these paths
+        #[arg(last = true)]
+        paths: Vec<String>,
     },
     /// Show differences
     Diff {
@@ -55,6 +115,16 @@ enum Commands {
         staged: bool,
         /// Compare specific files
         files: Vec<String>,
+        #[command(flatten)]
+        summary: SummaryArgs,
+        #[command(flatten)]
+        diff_options: DiffOptionArgs,
+        /// Mark changed words instead of lines (plain or color)
+        #[arg(long, value_name = "MODE", num_args = 0..=1, default_missing_value = "plain")]
+        word_diff: Option<WordDiffMode>,
+        /// Show changed words using only colors
+        #[arg(long, conflicts_with = "word_diff")]
+        color_words: bool,
     },
     /// Create or switch branches
     Branch {
@@ -79,11 +149,23 @@ enum Commands {
     Merge {
         /// Branch to merge
         branch: String,
+        /// Skip the pre-merge hook
+        #[arg(long)]
+        no_verify: bool,
+        #[command(flatten)]
+        diff_options: DiffOptionArgs,
     },
     /// Show commit details
     Show {
         /// Commit hash (defaults to HEAD)
         commit: Option<String>,
+        /// Output format: medium, oneline, json or format:<template> (%h %an %ae %ad %cn %cd %s ...)
+        #[arg(long, visible_alias = "format", value_name = "FORMAT")]
+        pretty: Option<PrettyFormat>,
+        #[command(flatten)]
+        summary: SummaryArgs,
+        #[command(flatten)]
+        diff_options: DiffOptionArgs,
     },
     /// Reset changes
     Reset {
@@ -101,23 +183,458 @@ enum Commands {
         #[arg(long)]
         cached: bool,
     },
+    /// Apply a unified diff to the working tree and/or index
+    Apply {
+        /// Patch file (reads stdin if omitted or "-")
+        patch: Option<String>,
+        /// Apply to the index only, leaving the working tree untouched
+        #[arg(long, conflicts_with = "index")]
+        cached: bool,
+        /// Apply to both the working tree and the index
+        #[arg(long)]
+        index: bool,
+        /// Only check whether the patch applies
+        #[arg(long)]
+        check: bool,
+        /// Strip this many leading path components from file names
+        #[arg(short = 'p', default_value_t = 1)]
+        strip: usize,
+        /// Maximum lines of context to ignore when a hunk does not match
+        #[arg(long, default_value_t = 2)]
+        fuzz: usize,
+    },
+    /// Show which commit last changed each line of a file
+    Blame {
+        /// File to annotate
+        file: String,
+        /// Revision to annotate (defaults to HEAD)
+        revision: Option<String>,
+        /// Only annotate lines start,end or start,+count
+        #[arg(short = 'L', value_name = "RANGE")]
+        range: Option<String>,
+    },
+    /// Read and write configuration (`KEY` gets a value, `KEY VALUE` sets it)
+    #[command(group(ArgGroup::new("scope")), group(ArgGroup::new("action")))]
+    Config {
+        /// Use the system-wide file
+        #[arg(long, group = "scope")]
+        system: bool,
+        /// Use the current user's ~/.nvcsconfig
+        #[arg(long, group = "scope")]
+        global: bool,
+        /// Use the repository's .nvcs/config
+        #[arg(long, group = "scope")]
+        local: bool,
+        /// Print the value of a key
+        #[arg(long, value_name = "KEY", group = "action")]
+        get: Option<String>,
+        /// Set a key to a value
+        #[arg(long, num_args = 2, value_names = ["KEY", "VALUE"], group = "action")]
+        set: Option<Vec<String>>,
+        /// Remove a key
+        #[arg(long, value_name = "KEY", group = "action")]
+        unset: Option<String>,
+        /// List all settings
+        #[arg(short, long, group = "action")]
+        list: bool,
+        /// Prefix listed settings with their scope
+        #[arg(long, requires = "list")]
+        show_scope: bool,
+        #[arg(num_args = 0..=2, value_names = ["KEY", "VALUE"], conflicts_with = "action")]
+        args: Vec<String>,
+    },
+    /// Show which ignore rule, if any, matches each path
+    CheckIgnore {
+        /// Paths relative to the repository root
+        #[arg(required = true)]
+        paths: Vec<String>,
+        /// Print the matching rule as source:line:pattern
+        #[arg(short, long)]
+        verbose: bool,
+        /// With --verbose, also list paths that match no rule
+        #[arg(short, long, requires = "verbose")]
+        non_matching: bool,
+    },
+    /// Show the attributes of paths (`ATTR... PATH...`, or `ATTR... -- PATH...`)
+    CheckAttr {
+        /// Show every attribute that is set on the paths, which are then all the arguments
+        #[arg(short, long)]
+        all: bool,
+        args: Vec<String>,
+        /// Paths after `--`, so every argument before it is an attribute
+        #[arg(last = true)]
+        paths: Vec<String>,
+    },
+    /// Set aside uncommitted changes (defaults to push)
+    Stash {
+        #[command(subcommand)]
+        command: Option<StashCommand>,
+    },
+    /// Apply the changes introduced by existing commits onto HEAD
+    CherryPick {
+        /// Commits to pick, in order
+        #[arg(required_unless_present_any = ["resume", "abort"])]
+        revisions: Vec<String>,
+        /// Append "(cherry picked from commit ...)" to the message
+        #[arg(short = 'x')]
+        record_origin: bool,
+        /// Parent number to diff against when picking a merge commit
+        #[arg(short = 'm', long, value_name = "PARENT")]
+        mainline: Option<usize>,
+        /// Apply the changes without committing them
+        #[arg(short = 'n', long)]
+        no_commit: bool,
+        /// Continue after resolving conflicts
+        #[arg(long = "continue", conflicts_with_all = ["abort", "revisions"])]
+        resume: bool,
+        /// Give up and return to the original HEAD
+        #[arg(long, conflicts_with = "revisions")]
+        abort: bool,
+    },
+    /// Create commits that undo the changes of existing commits
+    Revert {
+        /// Commits to revert, in order
+        #[arg(required_unless_present_any = ["resume", "abort"])]
+        revisions: Vec<String>,
+        /// Parent number to diff against when reverting a merge commit
+        #[arg(short = 'm', long, value_name = "PARENT")]
+        mainline: Option<usize>,
+        /// Apply the inverse changes without committing them
+        #[arg(short = 'n', long)]
+        no_commit: bool,
+        /// Continue after resolving conflicts
+        #[arg(long = "continue", conflicts_with_all = ["abort", "revisions"])]
+        resume: bool,
+        /// Give up and return to the original HEAD
+        #[arg(long, conflicts_with = "revisions")]
+        abort: bool,
+    },
+    /// Replay the current branch's commits on top of another commit
+    Rebase {
+        /// Commit or branch to replay onto
+        #[arg(required_unless_present_any = ["resume", "skip", "abort"])]
+        upstream: Option<String>,
+        /// Edit the list of commits to replay first
+        #[arg(short, long)]
+        interactive: bool,
+        /// Fold "fixup!" and "squash!" commits into the commits they name
+        #[arg(long)]
+        autosquash: bool,
+        /// Continue after resolving conflicts or amending
+        #[arg(long = "continue", conflicts_with_all = ["upstream", "skip", "abort"])]
+        resume: bool,
+        /// Drop the commit that stopped the rebase and carry on
+        #[arg(long, conflicts_with_all = ["upstream", "abort"])]
+        skip: bool,
+        /// Give up and return to the original branch
+        #[arg(long, conflicts_with = "upstream")]
+        abort: bool,
+    },
+    /// Binary search the history for the commit that introduced a bug
+    Bisect {
+        #[command(subcommand)]
+        command: BisectCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum StashCommand {
+    /// Save local changes and reset the working tree to HEAD
+    Push {
+        /// Description of the stash entry
+        #[arg(short, long)]
+        message: Option<String>,
+        /// Also stash untracked files
+        #[arg(short = 'u', long)]
+        include_untracked: bool,
+    },
+    /// List stash entries
+    List,
+    /// Show the changes in a stash entry
+    Show {
+        /// Entry to show, e.g. stash@{1} (defaults to the newest)
+        stash: Option<String>,
+        /// Show the full diff instead of a diffstat
+        #[arg(short, long)]
+        patch: bool,
+    },
+    /// Apply a stash entry to the working tree
+    Apply {
+        stash: Option<String>,
+    },
+    /// Apply a stash entry and drop it if it applied cleanly
+    Pop {
+        stash: Option<String>,
+    },
+    /// Delete a stash entry
+    Drop {
+        stash: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum BisectCommand {
+    /// Start bisecting, optionally marking a bad and some good commits
+    Start {
+        /// Known bad revision
+        bad: Option<String>,
+        /// Known good revisions
+        good: Vec<String>,
+    },
+    /// Mark a revision (defaults to HEAD) as good
+    Good {
+        revision: Option<String>,
+    },
+    /// Mark a revision (defaults to HEAD) as bad
+    Bad {
+        revision: Option<String>,
+    },
+    /// Mark revisions (defaults to HEAD) as untestable
+    Skip {
+        revisions: Vec<String>,
+    },
+    /// Stop bisecting and return to the original HEAD
+    Reset,
+    /// Show the marks made so far
+    Log,
+    /// Test each candidate with a command: exit 0 is good, 125 skips, other failures are bad
+    Run {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+}
+
+/// Flags selecting a condensed diff summary, shared by diff, show and log.
+#[derive(Args)]
+#[group(multiple = false)]
+struct SummaryArgs {
+    /// Show a diffstat with a histogram of changed lines
+    #[arg(long)]
+    stat: bool,
+    /// Show machine-readable insertion and deletion counts
+    #[arg(long)]
+    numstat: bool,
+    /// Show only the total of changed files and lines
+    #[arg(long)]
+    shortstat: bool,
+    /// Show only names and status of changed files
+    #[arg(long)]
+    name_status: bool,
+}
+
+impl SummaryArgs {
+    fn format(&self) -> Option<SummaryFormat> {
+        if self.stat {
+            Some(SummaryFormat::Stat)
+        } else if self.numstat {
+            Some(SummaryFormat::NumStat)
+        } else if self.shortstat {
+            Some(SummaryFormat::ShortStat)
+        } else if self.name_status {
+            Some(SummaryFormat::NameStatus)
+        } else {
+            None
+        }
+    }
+}
+
+/// Line matching options shared by diff, show and merge.
+#[derive(Args)]
+struct DiffOptionArgs {
+    /// Diff algorithm (myers, patience, histogram; default from diff.algorithm)
+    #[arg(long, value_name = "ALGORITHM")]
+    diff_algorithm: Option<DiffAlgorithm>,
+    /// Ignore whitespace when comparing lines
+    #[arg(short = 'w', long)]
+    ignore_all_space: bool,
+    /// Ignore changes in amount of whitespace
+    #[arg(short = 'b', long)]
+    ignore_space_change: bool,
+    /// Ignore changes whose lines are all blank
+    #[arg(long)]
+    ignore_blank_lines: bool,
+    /// Ignore carriage-return at the end of line
+    #[arg(long)]
+    ignore_cr_at_eol: bool,
+}
+
+impl DiffOptionArgs {
+    fn options(&self, config: &Config) -> Result<DiffOptions> {
+        let defaults = DiffOptions::from_config(config)?;
+        Ok(DiffOptions {
+            algorithm: self.diff_algorithm.unwrap_or(defaults.algorithm),
+            ignore_all_space: self.ignore_all_space,
+            ignore_space_change: self.ignore_space_change,
+            ignore_blank_lines: self.ignore_blank_lines,
+            ignore_cr_at_eol: self.ignore_cr_at_eol,
+            word_diff: None,
+        })
+    }
 }
 
 fn main() -> Result<()> {
-    let cli = Cli::parse();
+    let args: Vec<String> = std::env::args().collect();
+    // The config command must still work when the settings are broken
+    let config = match args.get(1).map(String::as_str) {
+        Some("config") => Config::default(),
+        _ => Config::load_current()?,
+    };
+
+    let builtins: Vec<String> = Cli::command()
+        .get_subcommands()
+        .flat_map(|command| std::iter::once(command.get_name()).chain(command.get_all_aliases()))
+        .chain(["help"])
+        .map(str::to_string)
+        .collect();
+    let cli = match alias::expand(args, &config, &builtins)? {
+        Expansion::Command(args) => Cli::parse_from(args),
+        Expansion::Shell(command, args) => std::process::exit(alias::run_shell(&command, &args)?),
+    };
+    if let Some(enabled) = config.color_ui()? {
+        colored::control::set_override(enabled);
+    }
 
     match cli.command {
         Commands::Init => init::execute(),
         Commands::Add { files, all } => add::execute(files, all),
-        Commands::Commit { message, author } => commit::execute(message, author),
+        Commands::Commit { message, author, amend, edit, fixup, squash, no_verify } => {
+            commit::execute(commit::CommitOptions { message, author, amend, edit, fixup, squash, no_verify })
+        }
         Commands::Status => status::execute(),
-        Commands::Log { count, oneline } => log::execute(count, oneline),
-        Commands::Diff { staged, files } => diff::execute(staged, files),
+        Commands::Log { revisions, count, oneline, pretty, summary, date_order, topo_order, first_parent, graph, decorate, author, grep, since, until, paths } => {
+            log::execute(log::LogOptions {
+                revisions,
+                count,
+                format: match pretty {
+                    Some(format) => format,
+                    None if oneline => PrettyFormat::Oneline,
+                    None => PrettyFormat::Medium,
+                },
+                summary: summary.format(),
+                // The graph needs every child before its parents
+                order: if topo_order || (graph && !date_order) {
+                    WalkOrder::Topo
+                } else if date_order {
+                    WalkOrder::DateOrder
+                } else {
+                    WalkOrder::Date
+                },
+                first_parent,
+                graph,
+                decorate: decorate || config.get_bool("log.decorate")?.unwrap_or(false),
+                author,
+                grep,
+                since,
+                until,
+                paths,
+            })
+        }
+        Commands::Diff { staged, files, summary, diff_options, word_diff, color_words } => {
+            let options = DiffOptions {
+                word_diff: if color_words { Some(WordDiffMode::Color) } else { word_diff },
+                ..diff_options.options(&config)?
+            };
+            diff::execute(staged, files, summary.format(), options)
+        }
         Commands::Branch { name, list, delete } => branch::execute(name, list, delete),
         Commands::Checkout { target, branch } => checkout::execute(target, branch),
-        Commands::Merge { branch } => merge::execute(branch),
-        Commands::Show { commit } => show::execute(commit),
+        Commands::Merge { branch, no_verify, diff_options } => merge::execute(branch, diff_options.options(&config)?, no_verify),
+        Commands::Show { commit, pretty, summary, diff_options } => {
+            show::execute(commit, pretty.unwrap_or(PrettyFormat::Medium), summary.format(), diff_options.options(&config)?)
+        }
         Commands::Reset { mode, target } => reset::execute(mode, target),
         Commands::Rm { files, cached } => rm::execute(files, cached),
+        Commands::Apply { patch, cached, index, check, strip, fuzz } => {
+            apply::execute(patch, cached, index, check, strip, fuzz)
+        }
+        Commands::Blame { file, revision, range } => blame::execute(file, revision, range),
+        Commands::CherryPick { revisions, record_origin, mainline, no_commit, resume, abort } => {
+            let options = SequenceOptions { record_origin, mainline, no_commit };
+            cherry_pick::execute(revisions, options, resume, abort)
+        }
+        Commands::Revert { revisions, mainline, no_commit, resume, abort } => {
+            let options = SequenceOptions { mainline, no_commit, ..SequenceOptions::default() };
+            revert::execute(revisions, options, resume, abort)
+        }
+        Commands::Rebase { upstream, interactive, autosquash, resume, skip, abort } => {
+            if resume {
+                rebase::resume()
+            } else if skip {
+                rebase::skip()
+            } else if abort {
+                rebase::abort()
+            } else {
+                rebase::start(upstream.unwrap_or_default(), interactive, autosquash || config.get_bool("rebase.autosquash")?.unwrap_or(false))
+            }
+        }
+        Commands::Config { system, global, local, get, set, unset, list, show_scope, args } => {
+            let scope = if system {
+                Some(ConfigScope::System)
+            } else if global {
+                Some(ConfigScope::Global)
+            } else if local {
+                Some(ConfigScope::Local)
+            } else {
+                None
+            };
+            let action = match (get, set, unset, list) {
+                (Some(key), ..) => ConfigAction::Get(key),
+                (_, Some(mut pair), ..) => {
+                    let value = pair.pop().unwrap_or_default();
+                    ConfigAction::Set(pair.pop().unwrap_or_default(), value)
+                }
+                (_, _, Some(key), _) => ConfigAction::Unset(key),
+                (_, _, _, true) => ConfigAction::List,
+                _ => {
+                    let mut args = args.into_iter();
+                    match (args.next(), args.next()) {
+                        (Some(key), Some(value)) => ConfigAction::Set(key, value),
+                        (Some(key), None) => ConfigAction::Get(key),
+                        _ => return Err(anyhow::anyhow!("Nothing to do; give a key or --list")),
+                    }
+                }
+            };
+            config::execute(action, scope, show_scope)
+        }
+        Commands::CheckIgnore { paths, verbose, non_matching } => {
+            // Like grep, exit with 1 when nothing matched
+            if !check_ignore::execute(paths, verbose, non_matching)? {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Commands::CheckAttr { all, mut args, paths } => {
+            let (names, paths) = if all {
+                (Vec::new(), args.into_iter().chain(paths).collect())
+            } else if !paths.is_empty() {
+                (args, paths)
+            } else if args.len() >= 2 {
+                let paths = args.split_off(1);
+                (args, paths)
+            } else {
+                return Err(anyhow::anyhow!("Give at least one attribute and one path"));
+            };
+            if paths.is_empty() || (!all && names.is_empty()) {
+                return Err(anyhow::anyhow!("Give at least one attribute and one path"));
+            }
+            check_attr::execute(names, paths, all)
+        }
+        Commands::Stash { command } => match command.unwrap_or(StashCommand::Push { message: None, include_untracked: false }) {
+            StashCommand::Push { message, include_untracked } => stash::push(message, include_untracked),
+            StashCommand::List => stash::list(),
+            StashCommand::Show { stash, patch } => stash::show(stash, patch),
+            StashCommand::Apply { stash } => stash::apply(stash),
+            StashCommand::Pop { stash } => stash::pop(stash),
+            StashCommand::Drop { stash } => stash::drop(stash),
+        },
+        Commands::Bisect { command } => match command {
+            BisectCommand::Start { bad, good } => bisect::start(bad, good),
+            BisectCommand::Good { revision } => bisect::mark(bisect::Mark::Good, revision.into_iter().collect()),
+            BisectCommand::Bad { revision } => bisect::mark(bisect::Mark::Bad, revision.into_iter().collect()),
+            BisectCommand::Skip { revisions } => bisect::mark(bisect::Mark::Skip, revisions),
+            BisectCommand::Reset => bisect::reset(),
+            BisectCommand::Log => bisect::log(),
+            BisectCommand::Run { command } => bisect::run(command),
+        },
     }
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::process::Command;
+use anyhow::Result;
+use crate::core::{Config, Repository};
+
+/// A command line after `alias.<name>` settings have been applied.
+#[derive(Debug)]
+pub enum Expansion {
+    /// Arguments to parse as an nvcs command line
+    Command(Vec<String>),
+    /// A `!` alias: a shell command and the arguments given after the alias
+    Shell(String, Vec<String>),
+}
+
+/// Replaces an alias in the subcommand position of `args` with its
+/// expansion, repeatedly, so aliases may refer to other aliases. Built-in
+/// commands always win over aliases of the same name.
+pub fn expand(mut args: Vec<String>, config: &Config, builtins: &[String]) -> Result<Expansion> {
+    let mut expanded: Vec<String> = Vec::new();
+    loop {
+        let Some(name) = args.get(1).cloned() else { return Ok(Expansion::Command(args)) };
+        if name.starts_with('-') || builtins.contains(&name) {
+            return Ok(Expansion::Command(args));
+        }
+        let Some(value) = config.get(&format!("alias.{}", name)) else {
+            return Ok(Expansion::Command(args));
+        };
+        if expanded.contains(&name) {
+            expanded.push(name);
+            return Err(anyhow::anyhow!("Alias loop detected: {}", expanded.join(" -> ")));
+        }
+
+        if let Some(command) = value.strip_prefix('!') {
+            return Ok(Expansion::Shell(command.to_string(), args.split_off(2)));
+        }
+        let words = split_words(value)
+            .map_err(|error| anyhow::anyhow!("Bad alias.{}: {}", name, error))?;
+        if words.is_empty() {
+            return Err(anyhow::anyhow!("Alias '{}' is empty", name));
+        }
+        let rest = args.split_off(2);
+        args.truncate(1);
+        args.extend(words);
+        args.extend(rest);
+        expanded.push(name);
+    }
+}
+
+/// Runs a `!` alias with `sh` from the repository root, passing `args` as
+/// positional parameters, and returns its exit code. `NVCS_PREFIX` holds the
+/// directory the alias was run from, relative to the root.
+pub fn run_shell(command: &str, args: &[String]) -> Result<i32> {
+    let current = std::env::current_dir()?;
+    let (root, prefix) = match Repository::find_repository() {
+        Ok(repo) => {
+            let prefix = current.strip_prefix(&repo.root).map(|path| path.to_path_buf()).unwrap_or_default();
+            (repo.root, prefix)
+        }
+        Err(_) => (current, Default::default()),
+    };
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", command))
+        .arg(command)
+        .args(args)
+        .current_dir(root)
+        .env("NVCS_PREFIX", prefix)
+        .status()?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Splits on whitespace, keeping text inside single or double quotes
+/// together and honouring backslash escapes outside single quotes.
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                let word = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some('\\') if c == '"' => word.extend(chars.next()),
+                        Some(next) => word.push(next),
+                        None => return Err(format!("unclosed {} quote", c)),
+                    }
+                }
+            }
+            '\\' => current.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() => words.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(current);
+    Ok(words)
+}
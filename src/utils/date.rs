@@ -0,0 +1,106 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+/// Parses dates as accepted by `--since` and `--until`: absolute dates such
+/// as `2024-03-01` or `2024-03-01 12:30`, RFC 3339 timestamps, `@<seconds>`,
+/// `now`, `today`, `yesterday` and relative forms like `3 days ago` or
+/// `2.weeks.ago`. Dates without a zone are taken as UTC.
+pub fn parse_date(input: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let input = input.trim();
+    let midnight = |date: NaiveDate| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+
+    match input.to_lowercase().as_str() {
+        "now" => return Ok(now),
+        "today" => return Ok(midnight(now.date_naive())),
+        "yesterday" => return Ok(midnight(now.date_naive() - Duration::days(1))),
+        _ => {}
+    }
+
+    if let Some(seconds) = input.strip_prefix('@') {
+        if let Some(date) = seconds.parse().ok().and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()) {
+            return Ok(date);
+        }
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+        return Ok(date.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(input, format) {
+            return Ok(Utc.from_utc_datetime(&date));
+        }
+    }
+    for format in ["%Y-%m-%d", "%Y/%m/%d"] {
+        if let Ok(date) = NaiveDate::parse_from_str(input, format) {
+            return Ok(midnight(date));
+        }
+    }
+
+    parse_relative(input, now).ok_or_else(|| anyhow::anyhow!("Invalid date: '{}'", input))
+}
+
+/// Parses a commit date that keeps its time zone: RFC 3339,
+/// `2024-03-01 12:30:00 +0200` or `@<seconds> +0200`. Anything else
+/// `parse_date` understands is taken as UTC.
+pub fn parse_signature_date(input: &str) -> Result<DateTime<FixedOffset>> {
+    let input = input.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+        return Ok(date);
+    }
+    if let Ok(date) = DateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S %z") {
+        return Ok(date);
+    }
+    if let Some((seconds, zone)) = input.strip_prefix('@').and_then(|rest| rest.split_once(' ')) {
+        let offset = DateTime::parse_from_str(&format!("1970-01-01 00:00:00 {}", zone.trim()), "%Y-%m-%d %H:%M:%S %z")
+            .map(|date| *date.offset());
+        let time = seconds.parse().ok().and_then(|seconds| Utc.timestamp_opt(seconds, 0).single());
+        if let (Ok(offset), Some(time)) = (offset, time) {
+            return Ok(time.with_timezone(&offset));
+        }
+    }
+    Ok(parse_date(input, Utc::now())?.fixed_offset())
+}
+
+/// Parses `<n> <unit> ago`, with spaces or dots between the words.
+fn parse_relative(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let words: Vec<String> = input
+        .split(|c: char| c == '.' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let (amount, unit) = match words.as_slice() {
+        [amount, unit, "ago"] | [amount, unit] => (amount.parse::<i64>().ok()?, *unit),
+        _ => return None,
+    };
+
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    let offset = match unit {
+        "second" | "sec" => Duration::seconds(amount),
+        "minute" | "min" => Duration::minutes(amount),
+        "hour" => Duration::hours(amount),
+        "day" => Duration::days(amount),
+        "week" => Duration::weeks(amount),
+        "month" => Duration::days(amount * 30),
+        "year" => Duration::days(amount * 365),
+        _ => return None,
+    };
+    Some(now - offset)
+}
+
+/// Describes how long before `now` a date was, e.g. `3 hours ago`.
+pub fn relative_date(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - date).num_seconds();
+    if seconds < 0 {
+        return "in the future".to_string();
+    }
+    let (amount, unit) = match seconds {
+        0..=89 => (seconds, "second"),
+        90..=5399 => ((seconds + 30) / 60, "minute"),
+        5400..=129_599 => ((seconds + 1800) / 3600, "hour"),
+        129_600..=1_209_599 => ((seconds + 43_200) / 86_400, "day"),
+        1_209_600..=5_183_999 => ((seconds + 302_400) / 604_800, "week"),
+        5_184_000..=31_535_999 => ((seconds + 1_296_000) / 2_592_000, "month"),
+        _ => ((seconds + 15_768_000) / 31_536_000, "year"),
+    };
+    format!("{} {}{} ago", amount, unit, if amount == 1 { "" } else { "s" })
+}
@@ -1,39 +1,483 @@
-use similar::{ChangeTag, TextDiff};
+use std::borrow::Cow;
+use std::hash::Hash;
+use std::str::FromStr;
+use anyhow::Result;
+use similar::algorithms::{self, Capture, Replace};
+use similar::{group_diff_ops, Algorithm, ChangeTag, DiffOp};
 use colored::*;
+use crate::core::Config;
+use crate::utils::histogram;
 
-pub fn generate_diff(old_content: &str, new_content: &str, filename: &str) -> String {
-    let diff = TextDiff::from_lines(old_content, new_content);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffAlgorithm {
+    #[default]
+    Myers,
+    Patience,
+    Histogram,
+}
+
+impl FromStr for DiffAlgorithm {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name {
+            "myers" | "default" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "histogram" => Ok(DiffAlgorithm::Histogram),
+            _ => Err(format!("unknown diff algorithm '{}' (use myers, patience or histogram)", name)),
+        }
+    }
+}
+
+/// How `--word-diff` marks changed words within a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WordDiffMode {
+    /// Wrap changes in `[-removed-]` and `{+added+}`
+    Plain,
+    /// Show removed words in red and added words in green
+    Color,
+}
+
+impl FromStr for WordDiffMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "plain" => Ok(WordDiffMode::Plain),
+            "color" => Ok(WordDiffMode::Color),
+            _ => Err(format!("unknown word diff mode '{}' (use plain or color)", name)),
+        }
+    }
+}
+
+/// Controls how lines are matched when computing a diff, and how the
+/// result is displayed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffOptions {
+    pub algorithm: DiffAlgorithm,
+    /// Ignore all whitespace when comparing lines (`-w`)
+    pub ignore_all_space: bool,
+    /// Ignore changes in the amount of whitespace (`-b`)
+    pub ignore_space_change: bool,
+    /// Ignore changes whose lines are all blank
+    pub ignore_blank_lines: bool,
+    /// Ignore a carriage return at the end of a line
+    pub ignore_cr_at_eol: bool,
+    /// Mark changed words instead of whole lines
+    pub word_diff: Option<WordDiffMode>,
+}
+
+impl DiffOptions {
+    /// The default options with the algorithm set by diff.algorithm, for
+    /// commands that diff without taking diff options of their own.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let algorithm = match config.get("diff.algorithm") {
+            Some(name) => name.parse().map_err(|error: String| anyhow::anyhow!("diff.algorithm: {}", error))?,
+            None => DiffAlgorithm::default(),
+        };
+        Ok(DiffOptions { algorithm, ..DiffOptions::default() })
+    }
+
+    /// Diffs two sequences with the selected algorithm.
+    fn diff_slices<T: Eq + Hash + Ord>(&self, old: &[T], new: &[T]) -> Result<Vec<DiffOp>> {
+        let mut hook = Replace::new(Capture::new());
+        let (old_range, new_range) = (0..old.len(), 0..new.len());
+        let result = match self.algorithm {
+            DiffAlgorithm::Myers => algorithms::diff(Algorithm::Myers, &mut hook, old, old_range, new, new_range),
+            DiffAlgorithm::Patience => algorithms::diff(Algorithm::Patience, &mut hook, old, old_range, new, new_range),
+            DiffAlgorithm::Histogram => histogram::diff(&mut hook, old, new),
+        };
+        result?;
+        Ok(hook.into_inner().into_ops())
+    }
+
+    /// Returns the form of `line` used for comparison.
+    pub fn normalize<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        if self.ignore_all_space {
+            return Cow::Owned(line.chars().filter(|c| !c.is_whitespace()).collect());
+        }
+        if self.ignore_space_change {
+            // Runs of whitespace count as one space and trailing whitespace
+            // is dropped, but indentation still matters
+            let mut normalized = String::with_capacity(line.len());
+            let mut in_space = false;
+            for c in line.trim_end().chars() {
+                if c.is_whitespace() {
+                    in_space = true;
+                    continue;
+                }
+                if in_space {
+                    normalized.push(' ');
+                    in_space = false;
+                }
+                normalized.push(c);
+            }
+            if line.ends_with('\n') {
+                normalized.push('\n');
+            }
+            return Cow::Owned(normalized);
+        }
+        if self.ignore_cr_at_eol {
+            if let Some(stripped) = line.strip_suffix("\r\n") {
+                return Cow::Owned(format!("{}\n", stripped));
+            }
+            if let Some(stripped) = line.strip_suffix('\r') {
+                return Cow::Borrowed(stripped);
+            }
+        }
+        Cow::Borrowed(line)
+    }
+}
+
+/// Line-level diff of two texts. Lines keep their terminators.
+pub struct LineDiff<'a> {
+    pub old_lines: Vec<&'a str>,
+    pub new_lines: Vec<&'a str>,
+    pub ops: Vec<DiffOp>,
+    ignore_blank_lines: bool,
+}
+
+impl LineDiff<'_> {
+    /// Whether an operation should be hidden under `--ignore-blank-lines`.
+    pub fn is_ignorable(&self, op: &DiffOp) -> bool {
+        if !self.ignore_blank_lines {
+            return false;
+        }
+        let blank = |line: &&str| line.trim().is_empty();
+        match op {
+            DiffOp::Equal { .. } => true,
+            _ => {
+                self.old_lines[op.old_range()].iter().all(blank)
+                    && self.new_lines[op.new_range()].iter().all(blank)
+            }
+        }
+    }
+
+    /// Groups the changes into hunks with `context` lines around them,
+    /// dropping hunks that consist only of ignorable changes.
+    pub fn grouped_ops(&self, context: usize) -> Vec<Vec<DiffOp>> {
+        group_diff_ops(self.ops.clone(), context)
+            .into_iter()
+            .filter(|group| !group.iter().all(|op| self.is_ignorable(op)))
+            .collect()
+    }
+}
+
+pub fn diff_lines<'a>(old_content: &'a str, new_content: &'a str, options: &DiffOptions) -> Result<LineDiff<'a>> {
+    let old_lines: Vec<&str> = old_content.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new_content.split_inclusive('\n').collect();
+    let old_keys: Vec<Cow<str>> = old_lines.iter().map(|line| options.normalize(line)).collect();
+    let new_keys: Vec<Cow<str>> = new_lines.iter().map(|line| options.normalize(line)).collect();
+
+    let ops = options.diff_slices(&old_keys, &new_keys)?;
+
+    Ok(LineDiff { old_lines, new_lines, ops, ignore_blank_lines: options.ignore_blank_lines })
+}
+
+pub fn generate_diff(old_content: &str, new_content: &str, filename: &str, options: &DiffOptions) -> Result<String> {
+    let diff = diff_lines(old_content, new_content, options)?;
+    let groups = diff.grouped_ops(3);
     let mut output = String::new();
-    
+    if groups.is_empty() {
+        return Ok(output);
+    }
+
     output.push_str(&format!("--- a/{}\n", filename));
     output.push_str(&format!("+++ b/{}\n", filename));
-    
-    for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
-        if idx > 0 {
-            output.push_str("@@ ... @@\n");
-        }
-        
-        for op in group {
-            for change in diff.iter_inline_changes(op) {
+
+    for group in groups {
+        output.push_str(&format!("{}\n", hunk_header(&group).cyan()));
+
+        if let Some(mode) = options.word_diff {
+            output.push_str(&word_diff_group(&diff, &group, mode, options)?);
+            continue;
+        }
+
+        for op in &group {
+            for change in op.iter_changes(&diff.old_lines, &diff.new_lines) {
                 let (sign, color) = match change.tag() {
                     ChangeTag::Delete => ("-", "red"),
                     ChangeTag::Insert => ("+", "green"),
                     ChangeTag::Equal => (" ", "white"),
                 };
-                
+
+                let line = change.value();
                 output.push_str(&format!(
                     "{}{}\n",
                     sign.color(color),
-                    change.value().trim_end().color(color)
+                    line.strip_suffix('\n').unwrap_or(line).color(color)
                 ));
+                if !line.ends_with('\n') {
+                    output.push_str("\\ No newline at end of file\n");
+                }
             }
         }
     }
-    
+
+    Ok(output)
+}
+
+/// Renders a hunk with changes marked word by word. Unchanged lines are
+/// printed as they are; changed lines are diffed as runs of words and
+/// whitespace.
+fn word_diff_group(diff: &LineDiff, group: &[DiffOp], mode: WordDiffMode, options: &DiffOptions) -> Result<String> {
+    let mut output = String::new();
+
+    for op in group {
+        let old_text: String = diff.old_lines[op.old_range()].concat();
+        let new_text: String = diff.new_lines[op.new_range()].concat();
+        if let DiffOp::Equal { .. } = op {
+            output.push_str(&old_text);
+            continue;
+        }
+
+        let old_words = split_words(&old_text);
+        let new_words = split_words(&new_text);
+        for word_op in options.diff_slices(&old_words, &new_words)? {
+            let removed: String = old_words[word_op.old_range()].concat();
+            let added: String = new_words[word_op.new_range()].concat();
+            match word_op {
+                DiffOp::Equal { .. } => output.push_str(&added),
+                _ => {
+                    output.push_str(&mark_words(&removed, ChangeTag::Delete, mode));
+                    output.push_str(&mark_words(&added, ChangeTag::Insert, mode));
+                }
+            }
+        }
+    }
+
+    if !output.is_empty() && !output.ends_with('\n') {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Splits text into runs of non-whitespace, runs of whitespace and single
+/// newlines, so that markers never span lines.
+fn split_words(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let end = index + c.len_utf8();
+        let boundary = match chars.peek() {
+            None => true,
+            Some(&(_, next)) => c == '\n' || next == '\n' || c.is_whitespace() != next.is_whitespace(),
+        };
+        if boundary {
+            words.push(&text[start..end]);
+            start = end;
+        }
+    }
+    words
+}
+
+fn mark_words(text: &str, tag: ChangeTag, mode: WordDiffMode) -> String {
+    let mut output = String::new();
+    for piece in text.split_inclusive('\n') {
+        let (words, newline) = match piece.strip_suffix('\n') {
+            Some(words) => (words, "\n"),
+            None => (piece, ""),
+        };
+        if !words.is_empty() {
+            let marked = match (mode, tag) {
+                (WordDiffMode::Plain, ChangeTag::Delete) => format!("[-{}-]", words),
+                (WordDiffMode::Plain, _) => format!("{{+{}+}}", words),
+                (WordDiffMode::Color, ChangeTag::Delete) => words.red().to_string(),
+                (WordDiffMode::Color, _) => words.green().to_string(),
+            };
+            output.push_str(&marked);
+        }
+        output.push_str(newline);
+    }
+    output
+}
+
+/// Builds the `@@ -a,b +c,d @@` header for a group of diff operations.
+fn hunk_header(group: &[DiffOp]) -> String {
+    let first = &group[0];
+    let last = &group[group.len() - 1];
+    let old_start = first.old_range().start;
+    let old_len = last.old_range().end - old_start;
+    let new_start = first.new_range().start;
+    let new_len = last.new_range().end - new_start;
+
+    // Empty ranges point at the line before the hunk, as in `diff -u`
+    let old_start = if old_len == 0 { old_start } else { old_start + 1 };
+    let new_start = if new_len == 0 { new_start } else { new_start + 1 };
+
+    format!("@@ -{},{} +{},{} @@", old_start, old_len, new_start, new_len)
+}
+
+/// Which condensed view of a diff to print instead of the full patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryFormat {
+    Stat,
+    NumStat,
+    ShortStat,
+    NameStatus,
+}
+
+/// Per-file line counts for a single change.
+#[derive(Debug, Clone)]
+pub struct FileStat {
+    pub path: String,
+    pub status: char,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Old and new sizes in bytes when either side is not text
+    pub binary: Option<(usize, usize)>,
+}
+
+impl FileStat {
+    /// Counts the changed lines between the two `texts`, or records the
+    /// `sizes` in bytes when there are none because a side is binary.
+    pub fn new(path: &str, status: char, texts: Option<(&str, &str)>, sizes: (usize, usize), options: &DiffOptions) -> Result<Self> {
+        let Some((old_text, new_text)) = texts else {
+            return Ok(Self {
+                path: path.to_string(),
+                status,
+                insertions: 0,
+                deletions: 0,
+                binary: Some(sizes),
+            });
+        };
+
+        let diff = diff_lines(old_text, new_text, options)?;
+        let (mut insertions, mut deletions) = (0, 0);
+        for op in diff.ops.iter().filter(|op| !diff.is_ignorable(op)) {
+            if !matches!(op, DiffOp::Equal { .. }) {
+                deletions += op.old_range().len();
+                insertions += op.new_range().len();
+            }
+        }
+
+        Ok(Self { path: path.to_string(), status, insertions, deletions, binary: None })
+    }
+}
+
+/// Returns the content as text unless it looks binary.
+pub fn text_content(content: &[u8]) -> Option<&str> {
+    if content.iter().take(8000).any(|&byte| byte == 0) {
+        return None;
+    }
+    std::str::from_utf8(content).ok()
+}
+
+pub fn format_summary(stats: &[FileStat], format: SummaryFormat) -> String {
+    let mut output = String::new();
+
+    match format {
+        SummaryFormat::NumStat => {
+            for stat in stats {
+                match stat.binary {
+                    Some(_) => output.push_str(&format!("-\t-\t{}\n", stat.path)),
+                    None => output.push_str(&format!("{}\t{}\t{}\n", stat.insertions, stat.deletions, stat.path)),
+                }
+            }
+        }
+        SummaryFormat::NameStatus => {
+            for stat in stats {
+                output.push_str(&format!("{}\t{}\n", stat.status, stat.path));
+            }
+        }
+        SummaryFormat::ShortStat => {
+            if !stats.is_empty() {
+                output.push_str(&format!("{}\n", shortstat_line(stats)));
+            }
+        }
+        SummaryFormat::Stat => {
+            if stats.is_empty() {
+                return output;
+            }
+            let name_width = stats.iter().map(|stat| stat.path.chars().count()).max().unwrap_or(0);
+            let max_changes = stats.iter().map(|stat| stat.insertions + stat.deletions).max().unwrap_or(0);
+            let count_width = max_changes.to_string().len().max(3);
+
+            // " name | count " precedes the bar
+            let terminal_width = std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.parse().ok())
+                .or_else(|| terminal_size::terminal_size().map(|(width, _)| width.0 as usize))
+                .unwrap_or(80);
+            let bar_width = terminal_width.saturating_sub(name_width + count_width + 5).max(10);
+
+            for stat in stats {
+                let name = format!("{:width$}", stat.path, width = name_width);
+                if let Some((old_size, new_size)) = stat.binary {
+                    output.push_str(&format!(
+                        " {} | {:>width$} {} -> {} bytes\n",
+                        name, "Bin", old_size, new_size, width = count_width
+                    ));
+                    continue;
+                }
+
+                let total = stat.insertions + stat.deletions;
+                let (plus, minus) = scale_bar(stat.insertions, stat.deletions, max_changes, bar_width);
+                output.push_str(&format!(
+                    " {} | {:>width$} {}{}\n",
+                    name,
+                    total,
+                    "+".repeat(plus).green(),
+                    "-".repeat(minus).red(),
+                    width = count_width
+                ));
+            }
+            output.push_str(&format!("{}\n", shortstat_line(stats)));
+        }
+    }
+
     output
 }
 
-pub fn print_diff(old_content: &str, new_content: &str, filename: &str) {
-    let diff_output = generate_diff(old_content, new_content, filename);
+/// Scales insertion and deletion counts so the largest change fits `width`,
+/// keeping at least one mark for any nonzero count.
+fn scale_bar(insertions: usize, deletions: usize, max_changes: usize, width: usize) -> (usize, usize) {
+    if max_changes <= width {
+        return (insertions, deletions);
+    }
+    let scale = |count: usize| {
+        if count == 0 {
+            0
+        } else {
+            (count * width / max_changes).max(1)
+        }
+    };
+    let mut total = scale(insertions + deletions);
+    if insertions > 0 && deletions > 0 {
+        total = total.max(2);
+    }
+    let mut plus = scale(insertions).min(total);
+    if deletions > 0 && plus == total && plus > 0 {
+        plus -= 1;
+    }
+    (plus, total - plus)
+}
+
+fn shortstat_line(stats: &[FileStat]) -> String {
+    let insertions: usize = stats.iter().map(|stat| stat.insertions).sum();
+    let deletions: usize = stats.iter().map(|stat| stat.deletions).sum();
+    let plural = |count: usize, word: &str| {
+        if count == 1 {
+            format!("{} {}", count, word)
+        } else {
+            format!("{} {}s", count, word)
+        }
+    };
+
+    let mut line = format!(" {} changed", plural(stats.len(), "file"));
+    if insertions > 0 || deletions == 0 {
+        line.push_str(&format!(", {}(+)", plural(insertions, "insertion")));
+    }
+    if deletions > 0 {
+        line.push_str(&format!(", {}(-)", plural(deletions, "deletion")));
+    }
+    line
+}
+
+pub fn print_diff(old_content: &str, new_content: &str, filename: &str, options: &DiffOptions) -> Result<()> {
+    let diff_output = generate_diff(old_content, new_content, filename, options)?;
     print!("{}", diff_output);
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use std::path::Path;
+use std::process::Command;
+use anyhow::Result;
+use crate::core::Config;
+
+/// Opens `path` in the user's editor (`$NVCS_EDITOR`, core.editor,
+/// `$VISUAL`, `$EDITOR`, then `vi`) and waits for it to exit.
+pub fn edit_file(path: &Path) -> Result<()> {
+    let from_env = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    let editor = from_env("NVCS_EDITOR")
+        .or_else(|| Config::load_current().ok()?.get("core.editor").map(str::to_string))
+        .or_else(|| from_env("VISUAL"))
+        .or_else(|| from_env("EDITOR"))
+        .unwrap_or_else(|| "vi".to_string());
+
+    // Run through the shell so editors with arguments ("code --wait") work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("There was a problem with the editor '{}'", editor));
+    }
+    Ok(())
+}
+
+/// Drops `#` comment lines and surrounding blank lines from edited text.
+pub fn strip_comments(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.starts_with('#')).collect();
+    lines.join("\n").trim().to_string()
+}
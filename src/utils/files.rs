@@ -1,64 +1,212 @@
-use std::path::{Path, PathBuf};
 use std::fs;
-use walkdir::WalkDir;
-use ignore::WalkBuilder;
+use std::path::{Component, Path, PathBuf};
+use ignore::Match;
+use ignore::gitignore::{Glob, Gitignore, GitignoreBuilder};
 use anyhow::Result;
+use crate::core::{Config, Repository};
 
-pub fn find_files_in_directory<P: AsRef<Path>>(dir: P) -> Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
-    
-    for entry in WalkBuilder::new(dir)
-        .hidden(false)
-        .git_ignore(true)
-        .build()
-    {
-        let entry = entry?;
-        if entry.file_type().map_or(false, |ft| ft.is_file()) {
-            files.push(entry.path().to_path_buf());
-        }
+/// Name of the per-directory ignore files
+pub const IGNORE_FILE: &str = ".nvcsignore";
+
+/// Metadata directories of other version control systems, never tracked
+/// whatever the ignore files say
+const FOREIGN_VCS_DIRS: [&str; 3] = [".git", ".hg", ".svn"];
+
+/// The ignore rules of a repository, using gitignore syntax. From lowest to
+/// highest precedence: the global excludes file, `.nvcs/info/exclude`, then
+/// the `.nvcsignore` files, where a deeper file overrides the ones above it.
+pub struct IgnoreRules {
+    root: PathBuf,
+    nvcs_dir: PathBuf,
+    global: Gitignore,
+    exclude: Gitignore,
+}
+
+/// The rule that decided whether a path is ignored.
+#[derive(Debug, Clone)]
+pub struct IgnoreMatch {
+    pub source: PathBuf,
+    /// 1-based line of the rule in `source`
+    pub line: usize,
+    pub pattern: String,
+    /// Whether this is a `!pattern` that re-includes the path
+    pub negated: bool,
+}
+
+impl IgnoreMatch {
+    pub fn is_ignored(&self) -> bool {
+        !self.negated
     }
-    
-    Ok(files)
 }
 
-pub fn is_ignored(path: &Path) -> bool {
-    let path_str = path.to_string_lossy();
-    
-    // Ignore .nvcs directory
-    if path_str.contains("/.nvcs/") || path_str.starts_with(".nvcs/") {
-        return true;
+impl IgnoreRules {
+    pub fn load(repo: &Repository) -> Result<Self> {
+        let config = Config::load(Some(repo))?;
+        let global = match global_excludes_file(&config) {
+            Some(path) => load_rules(&repo.root, &path)?,
+            None => Gitignore::empty(),
+        };
+        Ok(Self {
+            root: repo.root.clone(),
+            nvcs_dir: repo.nvcs_dir.clone(),
+            global,
+            exclude: load_rules(&repo.root, &repo.nvcs_dir.join("info").join("exclude"))?,
+        })
     }
-    
-    // Ignore common patterns
-    let ignore_patterns = [
-        ".git/", ".svn/", ".hg/",
-        "target/", "build/", "dist/",
-        "node_modules/", ".vscode/", ".idea/",
-        "*.tmp", "*.log", "*.swp", "*.swo",
-        ".DS_Store", "Thumbs.db",
-    ];
-    
-    for pattern in &ignore_patterns {
-        if pattern.ends_with('/') {
-            if path_str.contains(pattern) {
-                return true;
+
+    /// Every file in the working directory that is not ignored, sorted.
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        self.walk(&self.root, &mut Vec::new(), &mut files)?;
+        files.sort();
+        Ok(files)
+    }
+
+    fn walk(&self, dir: &Path, stack: &mut Vec<Gitignore>, files: &mut Vec<PathBuf>) -> Result<()> {
+        stack.push(load_rules(dir, &dir.join(IGNORE_FILE))?);
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            // Nothing below an ignored directory can be re-included
+            if path == self.nvcs_dir
+                || (file_type.is_dir() && is_foreign_vcs_dir(&path))
+                || self.matched(stack, &path, file_type.is_dir()).is_ignore()
+            {
+                continue;
+            }
+            if file_type.is_dir() {
+                self.walk(&path, stack, files)?;
+            } else if file_type.is_file() || file_type.is_symlink() {
+                files.push(path);
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    /// Whether `path` (absolute, or relative to the repository root) is
+    /// ignored, either itself or through one of its directories.
+    pub fn is_ignored(&self, path: &Path) -> Result<bool> {
+        let path = self.absolute(path)?;
+        if path.starts_with(&self.nvcs_dir) {
+            return Ok(true);
+        }
+        let relative = path.strip_prefix(&self.root)?;
+        if relative.ancestors().skip(1).any(is_foreign_vcs_dir) {
+            return Ok(true);
+        }
+        Ok(self.explain(&path)?.is_some_and(|found| found.is_ignored()))
+    }
+
+    /// The rule deciding whether `path` is ignored, if any. A path inside an
+    /// ignored directory reports the rule that ignored the directory.
+    pub fn explain(&self, path: &Path) -> Result<Option<IgnoreMatch>> {
+        let path = self.absolute(path)?;
+        let names: Vec<_> = path.strip_prefix(&self.root)?.components().collect();
+        let mut stack = Vec::new();
+        let mut current = self.root.clone();
+        for (position, name) in names.iter().enumerate() {
+            stack.push(load_rules(&current, &current.join(IGNORE_FILE))?);
+            current.push(name);
+            let last = position + 1 == names.len();
+            let found = self.matched(&stack, &current, !last || current.is_dir());
+            if last || found.is_ignore() {
+                return Ok(match found {
+                    Match::None => None,
+                    Match::Ignore(glob) | Match::Whitelist(glob) => Some(describe(glob)),
+                });
             }
-        } else if pattern.contains('*') {
-            // Simple glob matching
-            let pattern = pattern.replace('*', "");
-            if path_str.ends_with(&pattern) {
-                return true;
+        }
+        Ok(None)
+    }
+
+    /// The first rule matching `path`, looking through the `.nvcsignore`
+    /// files from the innermost out and then the repository-wide files.
+    fn matched<'a>(&'a self, stack: &'a [Gitignore], path: &Path, is_dir: bool) -> Match<&'a Glob> {
+        stack.iter()
+            .rev()
+            .chain([&self.exclude, &self.global])
+            .map(|rules| rules.matched(path, is_dir))
+            .find(|found| !found.is_none())
+            .unwrap_or(Match::None)
+    }
+
+    /// Makes `path` absolute without `.` or `..` parts, checking it stays in
+    /// the working directory.
+    fn absolute(&self, path: &Path) -> Result<PathBuf> {
+        let mut absolute = PathBuf::new();
+        for component in self.root.join(path).components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    absolute.pop();
+                }
+                component => absolute.push(component),
             }
-        } else if path_str.contains(pattern) {
-            return true;
         }
+        if !absolute.starts_with(&self.root) {
+            return Err(anyhow::anyhow!("'{}' is outside the repository", path.display()));
+        }
+        Ok(absolute)
     }
-    
-    false
 }
 
+/// core.excludesFile, or `~/.config/nvcs/ignore` when it is not set.
+fn global_excludes_file(config: &Config) -> Option<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    match config.get("core.excludesFile") {
+        Some(path) => match (path.strip_prefix("~/"), home) {
+            (Some(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(PathBuf::from(path)),
+        },
+        None => std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".config")))
+            .map(|config_dir| config_dir.join("nvcs").join("ignore")),
+    }
+}
+
+/// Reads the patterns in `file`, matched relative to `root`. A missing file
+/// has no patterns; a bad pattern is reported and skipped.
+fn load_rules(root: &Path, file: &Path) -> Result<Gitignore> {
+    if !file.is_file() {
+        return Ok(Gitignore::empty());
+    }
+    let mut builder = GitignoreBuilder::new(root);
+    if let Some(error) = builder.add(file) {
+        eprintln!("Warning: {}", error);
+    }
+    Ok(builder.build()?)
+}
+
+fn describe(glob: &Glob) -> IgnoreMatch {
+    let source = glob.from().map(Path::to_path_buf).unwrap_or_default();
+    // A later duplicate of the same line is the one that wins
+    let line = fs::read_to_string(&source)
+        .ok()
+        .and_then(|content| {
+            let lines: Vec<&str> = content.lines().collect();
+            lines.iter().rposition(|line| line.trim_end() == glob.original())
+        })
+        .map_or(0, |index| index + 1);
+    IgnoreMatch {
+        source,
+        line,
+        pattern: glob.original().to_string(),
+        negated: glob.is_whitelist(),
+    }
+}
+
+/// Whether the last component of `path` names another system's metadata
+/// directory.
+fn is_foreign_vcs_dir(path: &Path) -> bool {
+    path.file_name().and_then(|name| name.to_str()).is_some_and(|name| FOREIGN_VCS_DIRS.contains(&name))
+}
+
+#[allow(dead_code)]
 pub fn get_relative_path<P: AsRef<Path>>(path: P, base: P) -> Result<PathBuf> {
     let path = path.as_ref().canonicalize()?;
     let base = base.as_ref().canonicalize()?;
     Ok(path.strip_prefix(base)?.to_path_buf())
-}
\ No newline at end of file
+}
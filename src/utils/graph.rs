@@ -0,0 +1,103 @@
+use std::cmp::Ordering;
+
+/// Draws the ASCII commit graph for `log --graph`. Commits must be fed in
+/// an order where children come before their parents.
+pub struct GraphRenderer {
+    /// The commit each column is waiting for
+    lanes: Vec<String>,
+}
+
+/// Lines of graph output belonging to one commit.
+pub struct GraphRows {
+    /// Connector rows to print before the commit line
+    pub before: Vec<String>,
+    /// Prefix for the commit line itself
+    pub commit: String,
+    /// Prefix for further lines describing the commit
+    pub padding: String,
+    /// Connector rows to print after the commit's lines
+    pub after: Vec<String>,
+}
+
+impl GraphRenderer {
+    pub fn new() -> Self {
+        Self { lanes: Vec::new() }
+    }
+
+    pub fn next(&mut self, hash: &str, parents: &[String]) -> GraphRows {
+        let mut before = Vec::new();
+        let mut after = Vec::new();
+
+        let column = match self.lanes.iter().position(|lane| lane == hash) {
+            Some(column) => column,
+            None => {
+                self.lanes.push(hash.to_string());
+                self.lanes.len() - 1
+            }
+        };
+
+        // Other lines of history that end at this commit join its column
+        let joining: Vec<usize> = (column + 1..self.lanes.len())
+            .filter(|&lane| self.lanes[lane] == hash)
+            .collect();
+        if !joining.is_empty() {
+            // Lanes right of the first joining one all shift left
+            before.push(self.row(|lane| if lane >= joining[0] { '/' } else { '|' }));
+            for &lane in joining.iter().rev() {
+                self.lanes.remove(lane);
+            }
+        }
+
+        let commit = self.row(|lane| if lane == column { '*' } else { '|' });
+        // New lanes for merge parents branch off after the commit's own lines
+        let padding = self.row(|lane| if lane == column && parents.is_empty() { ' ' } else { '|' });
+
+        match parents.split_first() {
+            None => {
+                if column + 1 < self.lanes.len() {
+                    after.push(self.row(|lane| match lane.cmp(&column) {
+                        Ordering::Less => '|',
+                        Ordering::Equal => ' ',
+                        Ordering::Greater => '/',
+                    }));
+                }
+                self.lanes.remove(column);
+            }
+            Some((first, rest)) => {
+                self.lanes[column] = first.clone();
+                let mut inserted = 0;
+                for parent in rest {
+                    if !self.lanes.contains(parent) {
+                        inserted += 1;
+                        self.lanes.insert(column + inserted, parent.clone());
+                    }
+                }
+                if inserted > 0 {
+                    after.push(self.row(|lane| if lane > column { '\\' } else { '|' }));
+                }
+            }
+        }
+
+        GraphRows { before, commit, padding, after }
+    }
+
+    /// Renders one row of lanes. Slashes sit between columns, so lane `n`
+    /// drawn as `/` or `\` lands just left of its own column.
+    fn row(&self, symbol: impl Fn(usize) -> char) -> String {
+        let mut cells = vec![' '; self.lanes.len() * 2];
+        for lane in 0..self.lanes.len() {
+            match symbol(lane) {
+                slash @ ('/' | '\\') if lane > 0 => cells[lane * 2 - 1] = slash,
+                other => cells[lane * 2] = other,
+            }
+        }
+        let row: String = cells.into_iter().collect();
+        row.trim_end().to_string()
+    }
+}
+
+impl Default for GraphRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+use similar::algorithms::{myers, DiffHook, NoFinishHook};
+
+/// Occurrence count above which a line is too common to anchor a split.
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Histogram diff: recursively splits both sides around the longest common
+/// run containing the rarest shared line, falling back to Myers where no
+/// line is rare enough.
+pub fn diff<T, D>(d: &mut D, old: &[T], new: &[T]) -> Result<(), D::Error>
+where
+    T: Eq + Hash,
+    D: DiffHook,
+{
+    diff_region(d, old, 0..old.len(), new, 0..new.len())?;
+    d.finish()
+}
+
+fn diff_region<T, D>(
+    d: &mut D,
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+) -> Result<(), D::Error>
+where
+    T: Eq + Hash,
+    D: DiffHook,
+{
+    let prefix = old[old_range.clone()]
+        .iter()
+        .zip(&new[new_range.clone()])
+        .take_while(|(a, b)| a == b)
+        .count();
+    if prefix > 0 {
+        d.equal(old_range.start, new_range.start, prefix)?;
+        old_range.start += prefix;
+        new_range.start += prefix;
+    }
+
+    let suffix = old[old_range.clone()]
+        .iter()
+        .rev()
+        .zip(new[new_range.clone()].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() && !new_range.is_empty() {
+        d.insert(old_range.start, new_range.start, new_range.len())?;
+    } else if new_range.is_empty() && !old_range.is_empty() {
+        d.delete(old_range.start, old_range.len(), new_range.start)?;
+    } else if !old_range.is_empty() {
+        match find_split(old, old_range.clone(), new, new_range.clone()) {
+            Some((old_start, new_start, len)) => {
+                diff_region(d, old, old_range.start..old_start, new, new_range.start..new_start)?;
+                d.equal(old_start, new_start, len)?;
+                diff_region(d, old, old_start + len..old_range.end, new, new_start + len..new_range.end)?;
+            }
+            None => {
+                let mut hook = NoFinishHook::new(&mut *d);
+                myers::diff(&mut hook, old, old_range.clone(), new, new_range.clone())?;
+            }
+        }
+    }
+
+    if suffix > 0 {
+        d.equal(old_range.end, new_range.end, suffix)?;
+    }
+    Ok(())
+}
+
+/// Finds the common run whose rarest line occurs least often in `old`,
+/// preferring longer runs on ties.
+fn find_split<T>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+) -> Option<(usize, usize, usize)>
+where
+    T: Eq + Hash,
+{
+    let mut occurrences: HashMap<&T, Vec<usize>> = HashMap::new();
+    for index in old_range.clone() {
+        occurrences.entry(&old[index]).or_default().push(index);
+    }
+
+    // (rarity, old start, new start, length)
+    let mut best: Option<(usize, usize, usize, usize)> = None;
+    let mut new_index = new_range.start;
+
+    while new_index < new_range.end {
+        let positions = match occurrences.get(&new[new_index]) {
+            Some(positions) if positions.len() <= MAX_CHAIN_LENGTH => positions,
+            _ => {
+                new_index += 1;
+                continue;
+            }
+        };
+
+        let mut next_index = new_index + 1;
+        for &old_index in positions {
+            let (mut old_start, mut new_start) = (old_index, new_index);
+            while old_start > old_range.start && new_start > new_range.start && old[old_start - 1] == new[new_start - 1] {
+                old_start -= 1;
+                new_start -= 1;
+            }
+            let (mut old_end, mut new_end) = (old_index + 1, new_index + 1);
+            while old_end < old_range.end && new_end < new_range.end && old[old_end] == new[new_end] {
+                old_end += 1;
+                new_end += 1;
+            }
+
+            let len = old_end - old_start;
+            let rarity = (old_start..old_end)
+                .map(|index| occurrences[&old[index]].len())
+                .min()
+                .unwrap_or(usize::MAX);
+            let better = match best {
+                None => true,
+                Some((best_rarity, _, _, best_len)) => rarity < best_rarity || (rarity == best_rarity && len > best_len),
+            };
+            if better {
+                best = Some((rarity, old_start, new_start, len));
+            }
+            next_index = next_index.max(new_end);
+        }
+        new_index = next_index;
+    }
+
+    best.map(|(_, old_start, new_start, len)| (old_start, new_start, len))
+}
@@ -1,3 +1,10 @@
 pub mod hash;
 pub mod diff;
-pub mod files;
\ No newline at end of file
+pub mod files;
+pub mod patch;
+pub mod histogram;
+pub mod graph;
+pub mod date;
+pub mod pretty;
+pub mod editor;
+pub mod alias;
@@ -0,0 +1,335 @@
+use std::fmt;
+use anyhow::Result;
+
+/// A single line of a hunk body, stored with its line terminator (if any).
+#[derive(Debug, Clone)]
+pub enum HunkLine {
+    Context(String),
+    Delete(String),
+    Insert(String),
+}
+
+impl HunkLine {
+    pub fn text(&self) -> &str {
+        match self {
+            HunkLine::Context(text) | HunkLine::Delete(text) | HunkLine::Insert(text) => text,
+        }
+    }
+
+    fn text_mut(&mut self) -> &mut String {
+        match self {
+            HunkLine::Context(text) | HunkLine::Delete(text) | HunkLine::Insert(text) => text,
+        }
+    }
+
+    fn is_context(&self) -> bool {
+        matches!(self, HunkLine::Context(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_count, self.new_start, self.new_count
+        )?;
+        for line in &self.lines {
+            let sign = match line {
+                HunkLine::Context(_) => ' ',
+                HunkLine::Delete(_) => '-',
+                HunkLine::Insert(_) => '+',
+            };
+            let text = line.text();
+            match text.strip_suffix('\n') {
+                Some(text) => writeln!(f, "{}{}", sign, text)?,
+                None => {
+                    writeln!(f, "{}{}", sign, text)?;
+                    writeln!(f, "\\ No newline at end of file")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The changes a patch makes to one file. A missing path means `/dev/null`.
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+    pub old_path: Option<String>,
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+impl FilePatch {
+    pub fn path(&self) -> &str {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_default()
+    }
+}
+
+/// The lines `lines` expects to find: context and deletions.
+fn old_lines(lines: &[HunkLine]) -> Vec<String> {
+    lines
+        .iter()
+        .filter(|line| !matches!(line, HunkLine::Insert(_)))
+        .map(|line| line.text().to_string())
+        .collect()
+}
+
+/// The lines `lines` leaves behind: context and insertions.
+fn new_lines(lines: &[HunkLine]) -> Vec<String> {
+    lines
+        .iter()
+        .filter(|line| !matches!(line, HunkLine::Delete(_)))
+        .map(|line| line.text().to_string())
+        .collect()
+}
+
+/// Result of applying the hunks of a file patch to some content.
+pub struct ApplyResult {
+    pub content: String,
+    pub rejected: Vec<Hunk>,
+    pub messages: Vec<String>,
+}
+
+/// Parses a unified diff, stripping `strip` leading path components from
+/// file names (like `patch -p`).
+pub fn parse_patch(text: &str, strip: usize) -> Result<Vec<FilePatch>> {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut patches = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if !(line.starts_with("--- ") && i + 1 < lines.len() && lines[i + 1].starts_with("+++ ")) {
+            i += 1;
+            continue;
+        }
+
+        let old_path = parse_path(&line[4..], strip);
+        let new_path = parse_path(&lines[i + 1][4..], strip);
+        if old_path.is_none() && new_path.is_none() {
+            return Err(anyhow::anyhow!("Patch line {}: both sides are /dev/null", i + 1));
+        }
+        i += 2;
+
+        let mut hunks = Vec::new();
+        while i < lines.len() && lines[i].starts_with("@@") {
+            let (mut hunk, line_no) = (parse_hunk_header(lines[i], i + 1)?, i + 1);
+            i += 1;
+
+            let (mut old_seen, mut new_seen) = (0, 0);
+            while old_seen < hunk.old_count || new_seen < hunk.new_count {
+                let Some(line) = lines.get(i) else {
+                    return Err(anyhow::anyhow!("Patch line {}: truncated hunk", line_no));
+                };
+                let body = line.get(1..).unwrap_or_default().to_string();
+                let hunk_line = match line.chars().next() {
+                    Some(' ') => HunkLine::Context(body),
+                    // Some tools drop the leading space on blank context lines
+                    Some('\n') | Some('\r') => HunkLine::Context(line.to_string()),
+                    Some('-') => HunkLine::Delete(body),
+                    Some('+') => HunkLine::Insert(body),
+                    Some('\\') => {
+                        strip_newline(&mut hunk);
+                        i += 1;
+                        continue;
+                    }
+                    _ => return Err(anyhow::anyhow!("Patch line {}: malformed hunk line", i + 1)),
+                };
+                if !matches!(hunk_line, HunkLine::Insert(_)) {
+                    old_seen += 1;
+                }
+                if !matches!(hunk_line, HunkLine::Delete(_)) {
+                    new_seen += 1;
+                }
+                hunk.lines.push(hunk_line);
+                i += 1;
+            }
+
+            if old_seen != hunk.old_count || new_seen != hunk.new_count {
+                return Err(anyhow::anyhow!("Patch line {}: hunk line counts do not match header", line_no));
+            }
+
+            // A trailing "\ No newline" marker belongs to the last line read
+            if lines.get(i).is_some_and(|line| line.starts_with('\\')) {
+                strip_newline(&mut hunk);
+                i += 1;
+            }
+            hunks.push(hunk);
+        }
+
+        patches.push(FilePatch { old_path, new_path, hunks });
+    }
+
+    Ok(patches)
+}
+
+fn strip_newline(hunk: &mut Hunk) {
+    if let Some(last) = hunk.lines.last_mut() {
+        let text = last.text_mut();
+        if text.ends_with('\n') {
+            text.pop();
+        }
+    }
+}
+
+fn parse_path(raw: &str, strip: usize) -> Option<String> {
+    // Drop an optional tab-separated timestamp
+    let raw = raw.trim_end_matches(['\n', '\r']);
+    let raw = raw.split('\t').next().unwrap_or(raw).trim();
+    if raw == "/dev/null" {
+        return None;
+    }
+    let components: Vec<&str> = raw.split('/').collect();
+    let skip = strip.min(components.len() - 1);
+    Some(components[skip..].join("/"))
+}
+
+fn parse_hunk_header(line: &str, line_no: usize) -> Result<Hunk> {
+    let malformed = || anyhow::anyhow!("Patch line {}: malformed hunk header", line_no);
+    let inner = line
+        .strip_prefix("@@ ")
+        .and_then(|rest| rest.split(" @@").next())
+        .ok_or_else(malformed)?;
+    let mut parts = inner.split_whitespace();
+    let old = parts.next().and_then(|p| p.strip_prefix('-')).ok_or_else(malformed)?;
+    let new = parts.next().and_then(|p| p.strip_prefix('+')).ok_or_else(malformed)?;
+
+    let parse_range = |range: &str| -> Result<(usize, usize)> {
+        let mut nums = range.splitn(2, ',');
+        let start = nums.next().unwrap_or_default().parse().map_err(|_| malformed())?;
+        let count = match nums.next() {
+            Some(count) => count.parse().map_err(|_| malformed())?,
+            None => 1,
+        };
+        Ok((start, count))
+    };
+    let (old_start, old_count) = parse_range(old)?;
+    let (new_start, new_count) = parse_range(new)?;
+
+    Ok(Hunk { old_start, old_count, new_start, new_count, lines: Vec::new() })
+}
+
+/// Applies the hunks of a file patch to `content`. Hunks are located near
+/// their recorded position, searching outwards for an offset match and then
+/// ignoring up to `fuzz` lines of leading and trailing context.
+pub fn apply_patch(content: &str, patch: &FilePatch, fuzz: usize) -> ApplyResult {
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+    let mut rejected = Vec::new();
+    let mut messages = Vec::new();
+    // Lines added minus lines removed so far, and the last hunk end
+    let mut delta: isize = 0;
+    let mut min_pos = 0;
+
+    for (number, hunk) in patch.hunks.iter().enumerate() {
+        // An empty old range names the line after which to insert
+        let start = if hunk.old_count == 0 { hunk.old_start } else { hunk.old_start.saturating_sub(1) };
+        let expected = (start as isize + delta).max(0) as usize;
+
+        match locate_hunk(&lines, hunk, expected, min_pos, fuzz) {
+            Some((pos, lead, trail)) => {
+                let body = &hunk.lines[lead..hunk.lines.len() - trail];
+                let old = old_lines(body);
+                let new = new_lines(body);
+                let offset = pos as isize - (expected + lead) as isize;
+
+                if offset != 0 {
+                    messages.push(format!("Hunk #{} succeeded at {} (offset {} lines).", number + 1, pos + 1, offset));
+                }
+                if lead > 0 || trail > 0 {
+                    messages.push(format!("Hunk #{} applied with fuzz {}.", number + 1, lead.max(trail)));
+                }
+
+                lines.splice(pos..pos + old.len(), new.iter().cloned());
+                delta += new.len() as isize - old.len() as isize + offset;
+                min_pos = pos + new.len();
+            }
+            None => {
+                messages.push(format!("Hunk #{} FAILED at {}.", number + 1, hunk.old_start));
+                rejected.push(hunk.clone());
+            }
+        }
+    }
+
+    ApplyResult { content: lines.concat(), rejected, messages }
+}
+
+fn locate_hunk(
+    lines: &[String],
+    hunk: &Hunk,
+    expected: usize,
+    min_pos: usize,
+    fuzz: usize,
+) -> Option<(usize, usize, usize)> {
+    let leading = hunk.lines.iter().take_while(|line| line.is_context()).count();
+    let trailing = hunk.lines.iter().rev().take_while(|line| line.is_context()).count();
+
+    for level in 0..=fuzz {
+        let lead = level.min(leading);
+        let trail = level.min(trailing);
+        if level > 0 && lead + trail == 0 {
+            break;
+        }
+        let kept = &hunk.lines[lead..hunk.lines.len() - trail];
+        // A fuzzed match must still be anchored by a context line
+        if level > 0 && !kept.iter().any(|line| line.is_context()) {
+            break;
+        }
+        let old = old_lines(kept);
+        let expected = expected + lead;
+
+        if old.is_empty() {
+            // Pure insertion without context: trust the position, clamped to the file
+            let pos = expected.clamp(min_pos, lines.len());
+            return Some((pos, lead, trail));
+        }
+        if old.len() > lines.len() {
+            continue;
+        }
+
+        let last_start = lines.len() - old.len();
+        let matches_at = |pos: usize| pos >= min_pos && pos <= last_start && lines[pos..pos + old.len()] == old[..];
+        for distance in 0..=lines.len() {
+            if matches_at(expected + distance) {
+                return Some((expected + distance, lead, trail));
+            }
+            if distance > 0 && distance <= expected && matches_at(expected - distance) {
+                return Some((expected - distance, lead, trail));
+            }
+            if expected + distance > last_start && distance > expected {
+                break;
+            }
+        }
+    }
+
+    None
+}
+
+/// Formats rejected hunks the way they are written to `.rej` files.
+pub fn format_rejects(patch: &FilePatch, hunks: &[Hunk]) -> String {
+    let mut output = String::new();
+    // Keep /dev/null for a created or deleted file so the rejects still apply
+    let side = |path: &Option<String>, prefix: &str| match path {
+        Some(path) => format!("{}/{}", prefix, path),
+        None => "/dev/null".to_string(),
+    };
+    output.push_str(&format!("--- {}\n", side(&patch.old_path, "a")));
+    output.push_str(&format!("+++ {}\n", side(&patch.new_path, "b")));
+    for hunk in hunks {
+        output.push_str(&hunk.to_string());
+    }
+    output
+}
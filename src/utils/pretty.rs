@@ -0,0 +1,282 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use anyhow::Result;
+use chrono::{DateTime, FixedOffset, Utc};
+use colored::*;
+use serde::Serialize;
+use crate::core::{Repository, Commit, Signature, Tree, TreeChange};
+use crate::core::attributes::Attributes;
+use crate::utils::diff::{DiffOptions, FileStat};
+use crate::utils::date::relative_date;
+
+/// Placeholder names, with longer names before their prefixes.
+const PLACEHOLDERS: [&str; 30] = [
+    "Creset", "Cred", "Cgreen", "Cblue", "Cyellow",
+    "an", "ae", "ad", "ar", "at", "ai",
+    "cn", "ce", "cd", "cr", "ct", "ci",
+    "H", "h", "T", "t", "P", "p", "s", "b", "B", "d", "D", "n", "%",
+];
+
+/// How `log` and `show` print each commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrettyFormat {
+    /// Hash, author, date and indented message
+    Medium,
+    /// Short hash and subject on one line
+    Oneline,
+    /// A `format:` template with `%` placeholders
+    Template(String),
+    /// One JSON object per commit
+    Json,
+}
+
+impl FromStr for PrettyFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(template) = s.strip_prefix("format:").or_else(|| s.strip_prefix("tformat:")) {
+            return Ok(PrettyFormat::Template(template.to_string()));
+        }
+        match s {
+            "medium" => Ok(PrettyFormat::Medium),
+            "oneline" => Ok(PrettyFormat::Oneline),
+            "json" => Ok(PrettyFormat::Json),
+            _ if s.contains('%') => Ok(PrettyFormat::Template(s.to_string())),
+            _ => Err(format!("unknown format '{}' (expected medium, oneline, json or format:<template>)", s)),
+        }
+    }
+}
+
+/// Formats a commit's date the way `log` and `show` print it, in the time
+/// zone it was recorded in.
+pub fn format_date(date: &DateTime<FixedOffset>) -> String {
+    date.format("%Y-%m-%d %H:%M:%S %z").to_string()
+}
+
+/// Expands the placeholders of a `format:` template for one commit.
+/// `decoration` holds the ref names pointing at the commit, already joined.
+pub fn expand_template(template: &str, hash: &str, commit: &Commit, decoration: &str) -> String {
+    let short = |hash: &str| hash[..hash.len().min(8)].to_string();
+    let (subject, body) = match commit.message.split_once('\n') {
+        Some((subject, body)) => (subject, body.trim_start_matches('\n')),
+        None => (commit.message.as_str(), ""),
+    };
+
+    let mut output = String::new();
+    let mut rest = template;
+    while let Some(position) = rest.find('%') {
+        output.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        match PLACEHOLDERS.iter().find(|name| rest.starts_with(*name)) {
+            Some(name) => {
+                let text = match *name {
+                    "Creset" => color_code("0"),
+                    "Cred" => color_code(&Color::Red.to_fg_str()),
+                    "Cgreen" => color_code(&Color::Green.to_fg_str()),
+                    "Cblue" => color_code(&Color::Blue.to_fg_str()),
+                    "Cyellow" => color_code(&Color::Yellow.to_fg_str()),
+                    "an" => commit.author.name.clone(),
+                    "ae" => commit.author.email.clone(),
+                    "ad" | "ai" => format_date(&commit.author.when),
+                    "ar" => relative_date(commit.author.time(), Utc::now()),
+                    "at" => commit.author.when.timestamp().to_string(),
+                    "cn" => commit.committer.name.clone(),
+                    "ce" => commit.committer.email.clone(),
+                    "cd" | "ci" => format_date(&commit.committer.when),
+                    "cr" => relative_date(commit.committer.time(), Utc::now()),
+                    "ct" => commit.committer.when.timestamp().to_string(),
+                    "H" => hash.to_string(),
+                    "h" => short(hash),
+                    "T" => commit.tree.clone(),
+                    "t" => short(&commit.tree),
+                    "P" => commit.parents.join(" "),
+                    "p" => commit.parents.iter().map(|parent| short(parent)).collect::<Vec<_>>().join(" "),
+                    "s" => subject.to_string(),
+                    "b" => body.to_string(),
+                    "B" => commit.message.clone(),
+                    "d" if decoration.is_empty() => String::new(),
+                    "d" => format!(" ({})", decoration),
+                    "D" => decoration.to_string(),
+                    "n" => "\n".to_string(),
+                    _ => "%".to_string(),
+                };
+                output.push_str(&text);
+                rest = &rest[name.len()..];
+            }
+            // Unknown placeholders are printed as written
+            None => output.push('%'),
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+/// The author and date lines of the medium format, followed by the
+/// committer and commit date when they differ from the author's.
+pub fn signature_lines(commit: &Commit) -> Vec<String> {
+    let mut lines = vec![
+        format!("{} {}", "Author:".bright_white(), commit.author),
+        format!("{} {}", "Date:".bright_white(), format_date(&commit.author.when)),
+    ];
+    if commit.committer.identity() != commit.author.identity() {
+        lines.push(format!("{} {}", "Commit:".bright_white(), commit.committer));
+    }
+    // Dates are shown to the second, so finer differences are not worth a line
+    if commit.committer.when.timestamp() != commit.author.when.timestamp()
+        || commit.committer.when.offset() != commit.author.when.offset()
+    {
+        lines.push(format!("{} {}", "CommitDate:".bright_white(), format_date(&commit.committer.when)));
+    }
+    lines
+}
+
+fn color_code(code: &str) -> String {
+    if control::SHOULD_COLORIZE.should_colorize() {
+        format!("\x1b[{}m", code)
+    } else {
+        String::new()
+    }
+}
+
+/// A commit as emitted by `--format=json`.
+#[derive(Serialize)]
+struct CommitRecord<'a> {
+    hash: &'a str,
+    parents: &'a [String],
+    tree: &'a str,
+    /// The committer's time in UTC, as before signatures carried their own
+    timestamp: DateTime<Utc>,
+    author: &'a Signature,
+    committer: &'a Signature,
+    message: &'a str,
+    files: Vec<FileRecord>,
+}
+
+#[derive(Serialize)]
+struct FileRecord {
+    path: String,
+    status: char,
+}
+
+/// Lists the files a commit changed relative to its first parent; a root
+/// commit is compared against an empty tree.
+pub fn commit_changes(repo: &Repository, commit: &Commit) -> Result<Vec<TreeChange>> {
+    let tree_obj = repo.load_object(&commit.tree)?;
+    let tree = tree_obj.as_tree().unwrap();
+
+    let parent_tree = match commit.parents.first() {
+        Some(parent_hash) => {
+            let parent_obj = repo.load_object(parent_hash)?;
+            let parent_commit = parent_obj.as_commit().unwrap();
+            let parent_tree = repo.load_object(&parent_commit.tree)?;
+            parent_tree.as_tree().unwrap().clone()
+        }
+        None => Tree::new(),
+    };
+
+    Ok(tree.changes_from(&parent_tree))
+}
+
+/// Serializes a commit and the files it changed as a single line of JSON.
+pub fn commit_json(repo: &Repository, hash: &str, commit: &Commit) -> Result<String> {
+    let files = commit_changes(repo, commit)?
+        .into_iter()
+        .map(|change| FileRecord { status: change.kind.letter(), path: change.path })
+        .collect();
+    let record = CommitRecord {
+        hash,
+        parents: &commit.parents,
+        tree: &commit.tree,
+        timestamp: commit.time(),
+        author: &commit.author,
+        committer: &commit.committer,
+        message: &commit.message,
+        files,
+    };
+    Ok(serde_json::to_string(&record)?)
+}
+
+/// Computes insertion and deletion counts for every file a commit changed.
+pub fn commit_file_stats(repo: &Repository, commit: &Commit, options: &DiffOptions) -> Result<Vec<FileStat>> {
+    let attributes = Attributes::load(repo)?;
+    let load = |hash: &Option<String>| -> Result<Vec<u8>> {
+        match hash {
+            Some(hash) => {
+                let blob_obj = repo.load_object(hash)?;
+                Ok(blob_obj.as_blob().map(|blob| blob.content.clone()).unwrap_or_default())
+            }
+            None => Ok(Vec::new()),
+        }
+    };
+
+    commit_changes(repo, commit)?
+        .iter()
+        .map(|change| {
+            let old = load(&change.old_hash)?;
+            let new = load(&change.new_hash)?;
+            attributes.file_stat(&change.path, change.kind.letter(), &old, &new, options)
+        })
+        .collect()
+}
+
+pub enum RefLabel {
+    Head(Option<String>),
+    Branch(String),
+    Tag(String),
+}
+
+/// Maps commit hashes to the refs pointing at them, HEAD first.
+pub fn ref_decorations(repo: &Repository) -> Result<HashMap<String, Vec<RefLabel>>> {
+    let mut decorations: HashMap<String, Vec<RefLabel>> = HashMap::new();
+    let current = repo.get_current_branch()?;
+
+    if let Some(head) = repo.get_head()? {
+        decorations.entry(head).or_default().push(RefLabel::Head(current.clone()));
+    }
+    for branch in repo.list_branches()? {
+        if current.as_ref() == Some(&branch) {
+            continue;
+        }
+        if let Some(hash) = repo.read_ref(&format!("refs/heads/{}", branch))? {
+            decorations.entry(hash).or_default().push(RefLabel::Branch(branch));
+        }
+    }
+    for tag in repo.list_tags()? {
+        if let Some(hash) = repo.read_ref(&format!("refs/tags/{}", tag))? {
+            decorations.entry(hash).or_default().push(RefLabel::Tag(tag));
+        }
+    }
+
+    Ok(decorations)
+}
+
+pub fn format_decoration(labels: &[RefLabel]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let labels: Vec<String> = labels
+        .iter()
+        .map(|label| match label {
+            RefLabel::Head(Some(branch)) => format!("{} {}", "HEAD ->".cyan().bold(), branch.green().bold()),
+            RefLabel::Head(None) => "HEAD".cyan().bold().to_string(),
+            RefLabel::Branch(branch) => branch.green().bold().to_string(),
+            RefLabel::Tag(tag) => format!("tag: {}", tag).yellow().bold().to_string(),
+        })
+        .collect();
+    format!(" {}{}{}", "(".yellow(), labels.join(&", ".yellow().to_string()), ")".yellow())
+}
+
+/// Joins ref names without color, as `%D` prints them.
+pub fn decoration_names(labels: &[RefLabel]) -> String {
+    labels
+        .iter()
+        .map(|label| match label {
+            RefLabel::Head(Some(branch)) => format!("HEAD -> {}", branch),
+            RefLabel::Head(None) => "HEAD".to_string(),
+            RefLabel::Branch(branch) => branch.clone(),
+            RefLabel::Tag(tag) => format!("tag: {}", tag),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
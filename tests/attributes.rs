@@ -0,0 +1,24 @@
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+use common::nvcs;
+
+#[test]
+fn checkout_converts_files_with_the_target_attributes() {
+    let dir = TempDir::new().unwrap();
+    nvcs(dir.path(), &["init"]);
+    fs::write(dir.path().join("base"), "base\n").unwrap();
+    nvcs(dir.path(), &["add", "base"]);
+    nvcs(dir.path(), &["commit", "-m", "first"]);
+    nvcs(dir.path(), &["checkout", "-b", "crlf"]);
+    fs::write(dir.path().join(".nvcsattributes"), "*.txt eol=crlf\n").unwrap();
+    fs::write(dir.path().join("a.txt"), "one\ntwo\n").unwrap();
+    nvcs(dir.path(), &["add", ".nvcsattributes", "a.txt"]);
+    nvcs(dir.path(), &["commit", "-m", "crlf text"]);
+    nvcs(dir.path(), &["checkout", "main"]);
+    assert!(!dir.path().join("a.txt").exists());
+
+    nvcs(dir.path(), &["checkout", "crlf"]);
+    assert_eq!(fs::read_to_string(dir.path().join("a.txt")).unwrap(), "one\r\ntwo\r\n");
+}
@@ -0,0 +1,31 @@
+// Not every test binary uses every helper
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use serde_json::Value;
+
+/// Runs `nvcs` in `dir` with configuration isolated from the host,
+/// returning its standard output.
+pub fn nvcs(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_nvcs"))
+        .args(args)
+        .current_dir(dir)
+        .env("HOME", dir)
+        .env("NVCS_CONFIG_GLOBAL", dir.join(".nvcsconfig-global"))
+        .env("NVCS_CONFIG_SYSTEM", dir.join(".nvcsconfig-system"))
+        .output()
+        .expect("failed to run nvcs");
+    assert!(output.status.success(), "nvcs {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Lists the top-level entries of the HEAD commit's tree.
+pub fn head_tree_files(dir: &Path) -> Vec<String> {
+    let commit: Value = serde_json::from_str(&nvcs(dir, &["show", "--pretty", "json"])).unwrap();
+    let tree = commit["tree"].as_str().unwrap();
+    let object = fs::read_to_string(dir.join(".nvcs/objects").join(&tree[..2]).join(&tree[2..])).unwrap();
+    let object: Value = serde_json::from_str(&object).unwrap();
+    object["Tree"]["entries"].as_object().unwrap().keys().cloned().collect()
+}
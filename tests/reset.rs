@@ -0,0 +1,24 @@
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+use common::{head_tree_files, nvcs};
+
+#[test]
+fn reset_hard_restores_the_target_files() {
+    let dir = TempDir::new().unwrap();
+    nvcs(dir.path(), &["init"]);
+    fs::write(dir.path().join("a"), "one\n").unwrap();
+    nvcs(dir.path(), &["add", "a"]);
+    nvcs(dir.path(), &["commit", "-m", "first"]);
+    fs::write(dir.path().join("a"), "two\n").unwrap();
+    fs::write(dir.path().join("b"), "b\n").unwrap();
+    nvcs(dir.path(), &["add", "a", "b"]);
+    nvcs(dir.path(), &["commit", "-m", "second"]);
+    fs::write(dir.path().join("a"), "local edit\n").unwrap();
+
+    nvcs(dir.path(), &["reset", "--mode", "hard", "HEAD~1"]);
+    assert_eq!(fs::read_to_string(dir.path().join("a")).unwrap(), "one\n");
+    assert!(!dir.path().join("b").exists());
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
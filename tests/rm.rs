@@ -0,0 +1,67 @@
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+use common::{head_tree_files, nvcs};
+
+/// A repository with `a` and `b` committed.
+fn committed_repo() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    nvcs(dir.path(), &["init"]);
+    fs::write(dir.path().join("a"), "a\n").unwrap();
+    fs::write(dir.path().join("b"), "b\n").unwrap();
+    nvcs(dir.path(), &["add", "a", "b"]);
+    nvcs(dir.path(), &["commit", "-m", "first"]);
+    dir
+}
+
+#[test]
+fn rm_drops_a_committed_file_from_the_next_tree() {
+    let dir = committed_repo();
+    nvcs(dir.path(), &["rm", "b"]);
+    assert!(!dir.path().join("b").exists());
+
+    nvcs(dir.path(), &["commit", "-m", "remove b"]);
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
+
+#[test]
+fn rm_cached_keeps_the_working_file() {
+    let dir = committed_repo();
+    nvcs(dir.path(), &["rm", "--cached", "b"]);
+    assert!(dir.path().join("b").exists());
+
+    nvcs(dir.path(), &["commit", "-m", "untrack b"]);
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
+
+#[test]
+fn add_all_stages_deleted_files() {
+    let dir = committed_repo();
+    fs::remove_file(dir.path().join("b")).unwrap();
+    nvcs(dir.path(), &["add", "--all"]);
+
+    nvcs(dir.path(), &["commit", "-m", "remove b"]);
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
+
+#[test]
+fn conflicted_merge_stages_clean_deletions() {
+    let dir = committed_repo();
+    nvcs(dir.path(), &["checkout", "-b", "other"]);
+    fs::write(dir.path().join("a"), "other\n").unwrap();
+    nvcs(dir.path(), &["add", "a"]);
+    nvcs(dir.path(), &["rm", "b"]);
+    nvcs(dir.path(), &["commit", "-m", "other"]);
+    nvcs(dir.path(), &["checkout", "main"]);
+    fs::write(dir.path().join("a"), "main\n").unwrap();
+    nvcs(dir.path(), &["add", "a"]);
+    nvcs(dir.path(), &["commit", "-m", "main"]);
+
+    nvcs(dir.path(), &["merge", "other"]);
+    assert!(!dir.path().join("b").exists());
+    fs::write(dir.path().join("a"), "resolved\n").unwrap();
+    nvcs(dir.path(), &["add", "a"]);
+    nvcs(dir.path(), &["commit", "-m", "merge other"]);
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
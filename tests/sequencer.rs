@@ -0,0 +1,50 @@
+mod common;
+
+use std::fs;
+use tempfile::TempDir;
+use common::{head_tree_files, nvcs};
+
+/// A repository whose second commit adds `b` on top of `a`.
+fn repo_adding_b() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    nvcs(dir.path(), &["init"]);
+    fs::write(dir.path().join("a"), "a\n").unwrap();
+    nvcs(dir.path(), &["add", "a"]);
+    nvcs(dir.path(), &["commit", "-m", "add a"]);
+    fs::write(dir.path().join("b"), "b\n").unwrap();
+    nvcs(dir.path(), &["add", "b"]);
+    nvcs(dir.path(), &["commit", "-m", "add b"]);
+    dir
+}
+
+#[test]
+fn revert_removes_an_added_file() {
+    let dir = repo_adding_b();
+    nvcs(dir.path(), &["revert", "HEAD"]);
+
+    assert!(!dir.path().join("b").exists());
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
+
+#[test]
+fn revert_no_commit_stages_the_deletion() {
+    let dir = repo_adding_b();
+    nvcs(dir.path(), &["revert", "--no-commit", "HEAD"]);
+    assert!(!dir.path().join("b").exists());
+
+    nvcs(dir.path(), &["commit", "-m", "drop b"]);
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}
+
+#[test]
+fn cherry_pick_no_commit_stages_the_deletion() {
+    let dir = repo_adding_b();
+    nvcs(dir.path(), &["branch", "topic"]);
+    nvcs(dir.path(), &["rm", "b"]);
+    nvcs(dir.path(), &["commit", "-m", "drop b"]);
+
+    nvcs(dir.path(), &["checkout", "topic"]);
+    nvcs(dir.path(), &["cherry-pick", "--no-commit", "main"]);
+    nvcs(dir.path(), &["commit", "-m", "pick drop b"]);
+    assert_eq!(head_tree_files(dir.path()), ["a"]);
+}